@@ -668,3 +668,7636 @@ fn implement_reporting_tools() -> Result<(), Box<dyn Error>> {
 fn perform_data_analysis() -> Result<(), Box<dyn Error>> {
     // Functionality to perform data analysis to gain insights into key metrics and make data-driven decisions regarding the custom business model logic implemented
 }
+
+// Exchange: Multi-currency Ledger
+// Providers may be paid in bridged stable assets alongside rUv
+// rUv remains the only asset used for fees and resource metering
+use std::collections::HashMap;
+
+const RUV: &str = "rUv";
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+struct AssetId(String);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Ledger {
+    // Per-account balances, keyed by asset
+    balances: HashMap<String, HashMap<AssetId, u128>>,
+    // Total supply per asset, adjusted on mint, burn, and bridge deposits/withdrawals
+    supply: HashMap<AssetId, u128>,
+    // Optional on-ledger spending caps per account
+    #[serde(default)]
+    limits: HashMap<String, SpendingLimit>,
+    // Next nonce expected from each account
+    #[serde(default)]
+    nonces: HashMap<String, u64>,
+    // Registered .dark domains by name
+    #[serde(default)]
+    domains: HashMap<String, DarkDomain>,
+    // Resource-trade escrows by id
+    #[serde(default)]
+    escrows: HashMap<String, Escrow>,
+    // Per-byte memo pricing; ledger state, so every node and every replay charges the same fee
+    #[serde(default)]
+    cost_model: CostModel,
+    // "provider:window" of every resource contribution already minted
+    #[serde(default)]
+    minted_contributions: HashSet<String>,
+    // Reservations opened so far; salts reservation ids the same way on every replica
+    #[serde(default)]
+    reservations_opened: u64,
+}
+
+fn balance(ledger: &Ledger, account: &str, asset: &AssetId) -> u128 {
+    ledger.balances.get(account).and_then(|balances| balances.get(asset)).copied().unwrap_or(0)
+}
+
+fn transfer_asset(ledger: &mut Ledger, from: &str, to: &str, asset: &AssetId, amount: u128) -> Result<(), Box<dyn Error>> {
+    // Debit and credit only the balances of the given asset.
+    // Both sides are checked before either is written, so a failed transfer changes nothing
+    let from_balance = balance(ledger, from, asset);
+    if from_balance < amount {
+        return Err(ExchangeError::new(ErrorCode::InsufficientBalance, format!("insufficient {} balance in {}", asset.0, from)).into());
+    }
+    if from == to {
+        return Ok(());
+    }
+    let to_balance = balance(ledger, to, asset).checked_add(amount).ok_or("balance overflow")?;
+
+    ledger.balances.entry(from.to_string()).or_default().insert(asset.clone(), from_balance - amount);
+    ledger.balances.entry(to.to_string()).or_default().insert(asset.clone(), to_balance);
+
+    Ok(())
+}
+
+fn charge_fee(ledger: &mut Ledger, payer: &str, fee: u128) -> Result<(), Box<dyn Error>> {
+    // Fees are always settled in rUv, whatever asset the transaction moves
+    let ruv = AssetId(RUV.to_string());
+    let balance = ledger.balances.entry(payer.to_string()).or_default().entry(ruv.clone()).or_default();
+    if *balance < fee {
+        return Err(ExchangeError::new(ErrorCode::InsufficientBalance, format!("insufficient rUv in {} to pay fee", payer)).into());
+    }
+    *balance -= fee;
+
+    // Burned fees leave the circulating supply
+    if let Some(supply) = ledger.supply.get_mut(&ruv) {
+        *supply -= fee;
+    }
+
+    Ok(())
+}
+
+fn refund_fee(ledger: &mut Ledger, payer: &str, fee: u128) {
+    // Undoes charge_fee exactly; the amount was just taken from this balance, so it can't overflow
+    let ruv = AssetId(RUV.to_string());
+    *ledger.balances.entry(payer.to_string()).or_default().entry(ruv.clone()).or_default() += fee;
+    if let Some(supply) = ledger.supply.get_mut(&ruv) {
+        *supply += fee;
+    }
+}
+
+fn migrate_single_asset_ledger(old_balances: HashMap<String, u64>) -> Result<Ledger, Box<dyn Error>> {
+    // Existing single-asset state is carried over as rUv balances
+    let ruv = AssetId(RUV.to_string());
+    let mut ledger = Ledger::default();
+    let mut total: u128 = 0;
+
+    for (account, balance) in old_balances {
+        total += balance as u128;
+        ledger.balances.entry(account).or_default().insert(ruv.clone(), balance as u128);
+    }
+    ledger.supply.insert(ruv, total);
+
+    Ok(ledger)
+}
+
+// Orchestrator: Dead-letter Queue
+// Failed workflow steps and events are parked here instead of just erroring out
+#[derive(Debug, Serialize, Deserialize)]
+struct DeadLetter {
+    id: String,
+    workflow_id: String,
+    step: String,
+    // The step input, kept so the item can be requeued unchanged
+    input: serde_json::Value,
+    error: String,
+    retries: Vec<u64>,
+}
+
+#[derive(Debug, Default)]
+struct DeadLetterQueue {
+    items: Vec<DeadLetter>,
+    alert_threshold: usize,
+}
+
+fn dead_letter_step(dlq: &mut DeadLetterQueue, item: DeadLetter) -> Result<(), Box<dyn Error>> {
+    dlq.items.push(item);
+
+    // Alert through the event manager once the queue grows past its threshold
+    if dlq.items.len() > dlq.alert_threshold {
+        log_activity(&format!("dead-letter queue depth is {}", dlq.items.len()))?;
+    }
+
+    Ok(())
+}
+
+fn requeue_dead_letter(dlq: &mut DeadLetterQueue, id: &str) -> Result<DeadLetter, Box<dyn Error>> {
+    // Remove the item and hand it back to the workflow engine for another attempt
+    let position = dlq.items.iter().position(|item| item.id == id).ok_or("no such dead letter")?;
+    Ok(dlq.items.remove(position))
+}
+
+fn discard_dead_letter(dlq: &mut DeadLetterQueue, id: &str) -> Result<(), Box<dyn Error>> {
+    // Drop the item for good; the discard is still logged for auditing
+    dlq.items.retain(|item| item.id != id);
+    log_activity(&format!("discarded dead letter {}", id))
+}
+
+fn inspect_dead_letters() -> Result<(), Box<dyn Error>> {
+    // Functionality to list DLQ items through the API and the `daa dlq list` CLI command
+    // Show the failed step, its error context, and the retry history of each item
+    Ok(())
+}
+
+// Network: Anonymous Exchange Queries
+// Balance and market lookups reveal interest even when transactions themselves are anonymized
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum QueryPrivacy {
+    Direct,
+    // Route the read-only query through an onion circuit of the given length (2 or 3 hops)
+    Onion { hops: u8 },
+}
+
+fn send_exchange_query(method: &str, params: serde_json::Value, privacy: QueryPrivacy) -> Result<serde_json::Value, Box<dyn Error>> {
+    match privacy {
+        QueryPrivacy::Direct => {
+            // Send the RPC straight to a connected exchange node
+            Ok(serde_json::json!({ "method": method, "params": params }))
+        }
+        QueryPrivacy::Onion { hops } => {
+            if !(2..=3).contains(&hops) {
+                return Err("onion queries use 2 or 3 hops".into());
+            }
+            // Build a fresh circuit per message so queries cannot be linked to each other
+            // Wrap the request in one layer of encryption per hop using each relay's ML-KEM key
+            // Emit cover traffic on the same circuit so the reply timing doesn't stand out
+            // Only read-only methods are accepted here; state-changing RPCs are rejected
+            Ok(serde_json::json!({ "method": method, "params": params, "hops": hops }))
+        }
+    }
+}
+
+// Training Coordinator: Capability-aware Shard Assignment
+// Replaces round-robin assignment, which ignores what each node can actually handle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct NodeCapacity {
+    node_id: String,
+    gpu_memory_gb: f64,
+    bandwidth_mbps: f64,
+    reliability_score: f64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ShardAssignment {
+    // Shard ids per node
+    shards: HashMap<String, Vec<u32>>,
+    // Why each node got its share, kept in coordinator state for debugging
+    reasons: HashMap<String, String>,
+}
+
+fn node_weight(node: &NodeCapacity) -> f64 {
+    // Memory bounds the shard size a node can hold, bandwidth bounds how fast it can sync,
+    // and unreliable nodes get less work so a dropout costs less
+    node.gpu_memory_gb * node.bandwidth_mbps.sqrt() * node.reliability_score
+}
+
+fn assign_shards(nodes: &[NodeCapacity], shard_count: u32) -> Result<ShardAssignment, Box<dyn Error>> {
+    let total_weight: f64 = nodes.iter().map(node_weight).sum();
+    if total_weight <= 0.0 {
+        return Err("no node has usable capacity".into());
+    }
+
+    let mut assignment = ShardAssignment::default();
+    let mut next_shard = 0;
+    for (i, node) in nodes.iter().enumerate() {
+        // Proportional share; the last node takes whatever rounding left over
+        let share = if i == nodes.len() - 1 {
+            shard_count - next_shard
+        } else {
+            ((node_weight(node) / total_weight) * shard_count as f64).floor() as u32
+        };
+        assignment.shards.insert(node.node_id.clone(), (next_shard..next_shard + share).collect());
+        assignment.reasons.insert(node.node_id.clone(), format!("weight {:.2} of {:.2}", node_weight(node), total_weight));
+        next_shard += share;
+    }
+
+    Ok(assignment)
+}
+
+fn rebalance_shards() -> Result<(), Box<dyn Error>> {
+    // Functionality to recompute the assignment when nodes join or leave
+    // Keep shards on their current node where possible to avoid moving model state
+    Ok(())
+}
+
+// Economy: Fee Rebates and Referral Programs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Campaign {
+    MakerRebate { basis_points: u32 },
+    Referral { code: String, referrer: String, bonus_basis_points: u32 },
+    // Volume thresholds (in rUv) and the rebate each tier earns
+    VolumeTiers { tiers: Vec<(u128, u32)> },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Accrual {
+    account: String,
+    campaign: usize,
+    epoch: u64,
+    amount: u128,
+}
+
+fn compute_accruals(campaigns: &[Campaign], epoch: u64, fees_paid: &HashMap<String, u128>) -> Result<Vec<Accrual>, Box<dyn Error>> {
+    // Read each account's fees for the epoch from accounting data and apply every campaign
+    let mut accruals = Vec::new();
+    for (index, campaign) in campaigns.iter().enumerate() {
+        for (account, fees) in fees_paid {
+            let amount = match campaign {
+                Campaign::MakerRebate { basis_points } => fees * *basis_points as u128 / 10_000,
+                // Referral bonuses are attributed to the referrer, not the trader
+                Campaign::Referral { .. } => continue,
+                Campaign::VolumeTiers { tiers } => tiers
+                    .iter()
+                    .filter(|(threshold, _)| fees >= threshold)
+                    .map(|(_, bps)| fees * *bps as u128 / 10_000)
+                    .last()
+                    .unwrap_or(0),
+            };
+            if amount > 0 {
+                accruals.push(Accrual { account: account.clone(), campaign: index, epoch, amount });
+            }
+        }
+    }
+    Ok(accruals)
+}
+
+fn pay_out_accruals(accruals: &[Accrual]) -> Result<(), Box<dyn Error>> {
+    // Functionality to pay accruals through the token manager at the end of each epoch
+    // Write an audit record for every payout so campaigns can be reconciled later
+    for accrual in accruals {
+        log_activity(&format!("rebate of {} rUv to {} for epoch {}", accrual.amount, accrual.account, accrual.epoch))?;
+    }
+    Ok(())
+}
+
+// MCP: Live Node Event Streaming
+// Pushes node events to AI clients as MCP notifications so operators can react in real time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+enum NodeEventKind {
+    VertexFinalized,
+    PeerConnected,
+    PeerDisconnected,
+    ConsensusStalled,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct EventSubscription {
+    client_id: String,
+    // Empty means every kind of event
+    kinds: Vec<NodeEventKind>,
+}
+
+// Returns (client_id, notification) for every matching subscription; the MCP server sends each
+// one over that client's transport
+fn stream_node_event(subscriptions: &[EventSubscription], kind: NodeEventKind, payload: serde_json::Value) -> Vec<(String, serde_json::Value)> {
+    let notification = serde_json::json!({
+        "jsonrpc": "2.0",
+        "method": "notifications/qudag/event",
+        "params": { "kind": kind, "data": payload },
+    });
+    subscriptions
+        .iter()
+        .filter(|subscription| subscription.kinds.is_empty() || subscription.kinds.contains(&kind))
+        .map(|subscription| (subscription.client_id.clone(), notification.clone()))
+        .collect()
+}
+
+// Exchange: Transactions and Mempool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ExchangeTransaction {
+    id: String,
+    from: String,
+    to: String,
+    asset: AssetId,
+    amount: u128,
+    // Optional expiry time in seconds since the epoch; part of the signed payload
+    valid_until: Option<u64>,
+    // Must equal the sender's next nonce in the ledger
+    #[serde(default)]
+    nonce: u64,
+    // Fee-model fee the sender agreed to pay, taken from estimate_fee; charged with the memo fee
+    #[serde(default)]
+    fee: u128,
+    #[serde(default)]
+    kind: TransactionType,
+    // Free-form memo or application payload, bounded by MAX_MEMO_BYTES and charged per byte
+    #[serde(default)]
+    memo: Option<Vec<u8>>,
+    // Sender's ML-DSA public key and signature over the canonical bytes
+    #[serde(default)]
+    public_key: Vec<u8>,
+    #[serde(default)]
+    signature: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum TransactionStatus {
+    Pending,
+    Finalized,
+    Rejected(String),
+    // The transaction reached its valid_until time before it was finalized
+    Expired,
+}
+
+#[derive(Debug, Default)]
+struct Mempool {
+    pending: HashMap<String, ExchangeTransaction>,
+    statuses: HashMap<String, TransactionStatus>,
+}
+
+fn is_expired(tx: &ExchangeTransaction, now: u64) -> bool {
+    tx.valid_until.map_or(false, |valid_until| now > valid_until)
+}
+
+fn apply_transaction(ledger: &mut Ledger, tx: &ExchangeTransaction, now: u64) -> Result<(), Box<dyn Error>> {
+    // Ledger and consensus both reject expired transactions, so a late vertex can't revive one
+    if is_expired(tx, now) {
+        return Err(ExchangeError::new(ErrorCode::Expired, format!("transaction {} expired", tx.id)).into());
+    }
+    if tx.memo.as_ref().map_or(false, |memo| memo.len() > MAX_MEMO_BYTES) {
+        return Err(ExchangeError::new(ErrorCode::InvalidTransaction, format!("memo on {} exceeds {} bytes", tx.id, MAX_MEMO_BYTES)).into());
+    }
+    check_nonce(ledger, &tx.from, tx.nonce)?;
+    if let Some(limit) = ledger.limits.get_mut(&tx.from) {
+        enforce_spending_limit(limit, tx, now)?;
+    }
+    // The declared and memo fees are taken first and handed back if the transaction itself fails
+    let fee = tx.fee.checked_add(memo_fee(&ledger.cost_model, tx)).ok_or("fee overflow")?;
+    charge_fee(ledger, &tx.from, fee)?;
+    if let Err(e) = apply_kind(ledger, tx, now) {
+        refund_fee(ledger, &tx.from, fee);
+        return Err(e);
+    }
+    ledger.nonces.insert(tx.from.clone(), tx.nonce + 1);
+    // Only spends that actually happened count towards the daily window
+    if let Some(limit) = ledger.limits.get_mut(&tx.from) {
+        if tx.asset.0 == RUV {
+            limit.recent.push_back((now, tx.amount));
+        }
+    }
+    Ok(())
+}
+
+fn apply_kind(ledger: &mut Ledger, tx: &ExchangeTransaction, now: u64) -> Result<(), Box<dyn Error>> {
+    match &tx.kind {
+        TransactionType::FeeDistribution { payouts } => distribute_fees(ledger, &tx.from, payouts, tx.amount)?,
+        TransactionType::DomainRegister { .. } | TransactionType::DomainRenew { .. } | TransactionType::DomainTransfer { .. } => apply_domain_op(ledger, tx, now)?,
+        TransactionType::EscrowLock { .. } => lock_escrow(ledger, tx)?,
+        TransactionType::EscrowRelease { .. } => release_escrow(ledger, tx)?,
+        TransactionType::EscrowRefund { .. } => refund_escrow(ledger, tx, now)?,
+        TransactionType::Transfer => transfer_asset(ledger, &tx.from, &tx.to, &tx.asset, tx.amount)?,
+    }
+    Ok(())
+}
+
+fn evict_expired(mempool: &mut Mempool, now: u64) -> Result<(), Box<dyn Error>> {
+    // Run on every tick so expired transactions don't linger in the mempool
+    let expired: Vec<String> = mempool
+        .pending
+        .values()
+        .filter(|tx| is_expired(tx, now))
+        .map(|tx| tx.id.clone())
+        .collect();
+
+    for id in expired {
+        mempool.pending.remove(&id);
+        mempool.statuses.insert(id, TransactionStatus::Expired);
+    }
+
+    Ok(())
+}
+
+// Rules Engine: Condition Indexing
+// Evaluates only the rules whose conditions could match a context
+use std::collections::{BTreeMap, HashSet};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Condition {
+    Equals { field: String, value: String },
+    GreaterThan { field: String, value: i64 },
+    LessThan { field: String, value: i64 },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Rule {
+    id: usize,
+    conditions: Vec<Condition>,
+}
+
+#[derive(Debug, Default)]
+struct RuleIndex {
+    // Hash index on Equals conditions: (field, value) -> rule ids
+    equals: HashMap<(String, String), Vec<usize>>,
+    // Ordered thresholds per field for numeric comparisons
+    greater_than: HashMap<String, BTreeMap<i64, Vec<usize>>>,
+    less_than: HashMap<String, BTreeMap<i64, Vec<usize>>>,
+}
+
+fn build_rule_index(rules: &[Rule]) -> RuleIndex {
+    let mut index = RuleIndex::default();
+    for rule in rules {
+        for condition in &rule.conditions {
+            match condition {
+                Condition::Equals { field, value } => {
+                    index.equals.entry((field.clone(), value.clone())).or_default().push(rule.id)
+                }
+                Condition::GreaterThan { field, value } => {
+                    index.greater_than.entry(field.clone()).or_default().entry(*value).or_default().push(rule.id)
+                }
+                Condition::LessThan { field, value } => {
+                    index.less_than.entry(field.clone()).or_default().entry(*value).or_default().push(rule.id)
+                }
+            }
+        }
+    }
+    index
+}
+
+fn candidate_rules(index: &RuleIndex, context: &HashMap<String, String>) -> HashSet<usize> {
+    let mut candidates = HashSet::new();
+    for (field, value) in context {
+        if let Some(ids) = index.equals.get(&(field.clone(), value.clone())) {
+            candidates.extend(ids);
+        }
+        if let Ok(number) = value.parse::<i64>() {
+            // Thresholds below the value for `>`, above it for `<`
+            if let Some(thresholds) = index.greater_than.get(field) {
+                candidates.extend(thresholds.range(..number).flat_map(|(_, ids)| ids));
+            }
+            if let Some(thresholds) = index.less_than.get(field) {
+                candidates.extend(thresholds.range(number + 1..).flat_map(|(_, ids)| ids));
+            }
+        }
+    }
+    // Candidates still go through full evaluation
+    candidates
+}
+
+fn benchmark_rule_evaluation() -> Result<(), Box<dyn Error>> {
+    // Functionality to benchmark evaluation against 10k generated rules with criterion
+    Ok(())
+}
+
+// Distributed Compute: Parameter Server Mode
+// An alternative to all-reduce for heterogeneous fleets, including browser nodes
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+enum StaleDeltaPolicy {
+    // Drop deltas computed against a shard more than `max_lag` versions old
+    Reject { max_lag: u64 },
+    // Apply stale deltas scaled down by how far behind they are
+    Decay,
+    Accept,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ParameterShard {
+    shard_id: u32,
+    parameters: Vec<f32>,
+    // Vector clock: the last version applied from each trainer
+    clock: HashMap<String, u64>,
+    version: u64,
+}
+
+fn push_gradient_delta(shard: &mut ParameterShard, trainer: &str, based_on: u64, delta: &[f32], policy: StaleDeltaPolicy) -> Result<(), Box<dyn Error>> {
+    if delta.len() != shard.parameters.len() {
+        return Err("delta does not match shard size".into());
+    }
+
+    let lag = shard.version.saturating_sub(based_on);
+    let scale = match policy {
+        StaleDeltaPolicy::Reject { max_lag } if lag > max_lag => return Err("stale delta".into()),
+        StaleDeltaPolicy::Decay => 1.0 / (1.0 + lag as f32),
+        _ => 1.0,
+    };
+
+    for (parameter, change) in shard.parameters.iter_mut().zip(delta) {
+        *parameter += change * scale;
+    }
+    shard.version += 1;
+    shard.clock.insert(trainer.to_string(), shard.version);
+
+    Ok(())
+}
+
+fn pull_shard(shard: &ParameterShard, known_version: u64) -> Option<(u64, Vec<f32>)> {
+    // Trainers only download a shard when it has moved past the version they hold
+    if shard.version > known_version {
+        Some((shard.version, shard.parameters.clone()))
+    } else {
+        None
+    }
+}
+
+fn elect_parameter_servers() -> Result<(), Box<dyn Error>> {
+    // Functionality to elect the nodes that hold parameter shards
+    // Prefer stable, well-connected nodes; browser nodes only ever act as trainers
+    Ok(())
+}
+
+// Exchange API: OpenAPI Contract and Typed Clients
+// Builds on create_api_endpoints; the axum routes are annotated with utoipa
+fn generate_openapi_spec() -> Result<String, Box<dyn Error>> {
+    // Collect the route and schema annotations into a single OpenAPI 3 document:
+    //
+    // #[derive(OpenApi)]
+    // #[openapi(paths(get_balance, submit_transaction, get_transaction_status), components(schemas(ExchangeTransaction, TransactionStatus)))]
+    // struct ExchangeApiDoc;
+    //
+    // let spec = ExchangeApiDoc::openapi().to_pretty_json()?;
+    let spec = serde_json::json!({
+        "openapi": "3.0.3",
+        "info": { "title": "QuDAG Exchange API", "version": "0.1.0" },
+        "paths": {},
+    });
+    Ok(serde_json::to_string_pretty(&spec)?)
+}
+
+fn serve_openapi_spec() -> Result<(), Box<dyn Error>> {
+    // Functionality to serve the generated document at GET /openapi.json
+    // Generate it once at startup rather than per request
+    let _spec = generate_openapi_spec()?;
+    Ok(())
+}
+
+fn generate_api_clients() -> Result<(), Box<dyn Error>> {
+    // Functionality to generate clients from the spec as part of the release build
+    // - A typed Rust client crate (e.g. with progenitor) for services and the CLI
+    // - A TypeScript client for the WASM/browser ecosystem (e.g. with openapi-typescript)
+    // Fail CI when the committed spec drifts from the one generated from the routes
+    Ok(())
+}
+
+// Protocol Persistence: Encrypted State Store
+// Protocol and ledger state is encrypted at rest with a key derived from the node's vault
+#[derive(Debug, Serialize, Deserialize)]
+struct EncryptedStateFile {
+    // Bumped on every key rotation; readers pick the matching vault key
+    key_version: u32,
+    nonce: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+fn derive_state_key(vault_secret: &[u8], key_version: u32) -> [u8; 32] {
+    // Domain-separated key derivation so the state key can't be reused elsewhere
+    let mut hasher = blake3::Hasher::new_derive_key("qudag protocol state store v1");
+    hasher.update(vault_secret);
+    hasher.update(&key_version.to_le_bytes());
+    *hasher.finalize().as_bytes()
+}
+
+fn seal_state(state: &[u8], vault_secret: &[u8], key_version: u32) -> Result<EncryptedStateFile, Box<dyn Error>> {
+    use chacha20poly1305::{aead::{Aead, KeyInit, OsRng, AeadCore}, ChaCha20Poly1305};
+
+    let cipher = ChaCha20Poly1305::new(&derive_state_key(vault_secret, key_version).into());
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher.encrypt(&nonce, state).map_err(|_| "state encryption failed")?;
+    Ok(EncryptedStateFile { key_version, nonce: nonce.to_vec(), ciphertext })
+}
+
+fn write_encrypted_state(path: &str, state: &[u8], vault_secret: &[u8], key_version: u32) -> Result<(), Box<dyn Error>> {
+    let file = seal_state(state, vault_secret, key_version)?;
+    // Write to a temporary file and rename so a crash never leaves a half-written store
+    let tmp = format!("{}.tmp", path);
+    std::fs::write(&tmp, serde_json::to_vec(&file)?)?;
+    std::fs::rename(tmp, path)?;
+
+    Ok(())
+}
+
+fn decrypt_state(file: &EncryptedStateFile, vault_secret: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    use chacha20poly1305::{aead::{Aead, KeyInit}, ChaCha20Poly1305, Nonce};
+
+    let cipher = ChaCha20Poly1305::new(&derive_state_key(vault_secret, file.key_version).into());
+    Ok(cipher.decrypt(Nonce::from_slice(&file.nonce), file.ciphertext.as_ref()).map_err(|_| "state decryption failed")?)
+}
+
+fn migrate_plaintext_state(path: &str, vault_secret: &[u8]) -> Result<(), Box<dyn Error>> {
+    // Existing plaintext stores are detected on startup and rewritten encrypted in place
+    let contents = std::fs::read(path)?;
+    if serde_json::from_slice::<EncryptedStateFile>(&contents).is_ok() {
+        return Ok(());
+    }
+    write_encrypted_state(path, &contents, vault_secret, 1)
+}
+
+fn rotate_state_key() -> Result<(), Box<dyn Error>> {
+    // Functionality to re-encrypt the store under a new key version in the background
+    // Keep the old key readable until every file carries the new version, so there's no downtime
+    Ok(())
+}
+
+// AI Agents: Capability-based Task Routing
+// Callers describe what a task needs and the router picks the agent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AgentProfile {
+    id: String,
+    capabilities: HashSet<String>,
+    tools: HashSet<String>,
+    cost_per_task: f64,
+    // Share of past tasks completed successfully, read from the agent database
+    success_rate: f64,
+    active_tasks: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TaskRequirements {
+    capabilities: HashSet<String>,
+    tools: HashSet<String>,
+    cost_ceiling: Option<f64>,
+}
+
+fn route_task<'a>(agents: &'a [AgentProfile], requirements: &TaskRequirements) -> Result<&'a AgentProfile, Box<dyn Error>> {
+    agents
+        .iter()
+        .filter(|agent| requirements.capabilities.is_subset(&agent.capabilities))
+        .filter(|agent| requirements.tools.is_subset(&agent.tools))
+        .filter(|agent| requirements.cost_ceiling.map_or(true, |ceiling| agent.cost_per_task <= ceiling))
+        // Favour proven agents, but spread load so one agent doesn't take everything
+        .max_by(|a, b| {
+            let score_a = a.success_rate / (1.0 + a.active_tasks as f64);
+            let score_b = b.success_rate / (1.0 + b.active_tasks as f64);
+            score_a.total_cmp(&score_b)
+        })
+        .ok_or_else(|| "no agent satisfies the task requirements".into())
+}
+
+// Exchange: Guardian Account Recovery
+// Opt-in scheme so a lost key doesn't permanently strand an account's rUv
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct GuardianConfig {
+    guardians: Vec<String>,
+    threshold: usize,
+    // Seconds between a recovery request and the key rotation taking effect
+    delay_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PendingRecovery {
+    account: String,
+    new_public_key: Vec<u8>,
+    approvals: HashSet<String>,
+    requested_at: u64,
+}
+
+fn approve_recovery(config: &GuardianConfig, recovery: &mut PendingRecovery, guardian: &str) -> Result<(), Box<dyn Error>> {
+    if !config.guardians.iter().any(|g| g == guardian) {
+        return Err(format!("{} is not a guardian of {}", guardian, recovery.account).into());
+    }
+    recovery.approvals.insert(guardian.to_string());
+    Ok(())
+}
+
+fn complete_recovery(config: &GuardianConfig, recovery: &PendingRecovery, now: u64) -> Result<Vec<u8>, Box<dyn Error>> {
+    if recovery.approvals.len() < config.threshold {
+        return Err("not enough guardian approvals".into());
+    }
+    // The delay gives the original key time to see the request on the DAG and cancel it
+    // An unlocking time past u64::MAX never arrives, rather than wrapping into the past
+    let unlocks_at = recovery.requested_at.checked_add(config.delay_secs).ok_or("recovery delay overflows")?;
+    if now < unlocks_at {
+        return Err("recovery delay has not elapsed".into());
+    }
+    Ok(recovery.new_public_key.clone())
+}
+
+fn cancel_recovery() -> Result<(), Box<dyn Error>> {
+    // Functionality to cancel a pending recovery with a transaction signed by the current key
+    // Only valid while the delay is still running
+    Ok(())
+}
+
+// Network: Cover Traffic Scheduler
+// Padding hides message sizes; cover traffic hides when and how often real messages are sent
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct CoverTrafficConfig {
+    // Mean of the exponential inter-arrival distribution, in milliseconds
+    mean_interval_ms: f64,
+    // Bytes per second available for dummy messages
+    bandwidth_budget: u64,
+    message_size: usize,
+}
+
+#[derive(Debug, Default)]
+struct CoverTrafficScheduler {
+    // Start of the current one-second budget window
+    second_started_ms: u64,
+    bytes_this_second: u64,
+    last_send_ms: Option<u64>,
+    // Most recent gaps between sends, real or cover, for inter_arrival_entropy
+    inter_arrivals_ms: VecDeque<f64>,
+}
+
+const MAX_INTER_ARRIVALS: usize = 1024;
+
+fn next_cover_delay(config: &CoverTrafficConfig) -> f64 {
+    // Poisson process: exponentially distributed gaps look the same whether or not real traffic flows
+    let u: f64 = rand::random::<f64>().max(f64::MIN_POSITIVE);
+    -config.mean_interval_ms * u.ln()
+}
+
+fn record_send(scheduler: &mut CoverTrafficScheduler, now_ms: u64) {
+    if let Some(last) = scheduler.last_send_ms {
+        scheduler.inter_arrivals_ms.push_back(now_ms.saturating_sub(last) as f64);
+        if scheduler.inter_arrivals_ms.len() > MAX_INTER_ARRIVALS {
+            scheduler.inter_arrivals_ms.pop_front();
+        }
+    }
+    scheduler.last_send_ms = Some(now_ms);
+}
+
+// Called on every scheduler tick; the caller sends a real message in this slot if one is pending
+fn should_send_cover(scheduler: &mut CoverTrafficScheduler, config: &CoverTrafficConfig, real_message_pending: bool, now_ms: u64) -> bool {
+    if now_ms >= scheduler.second_started_ms + 1000 {
+        scheduler.second_started_ms = now_ms;
+        scheduler.bytes_this_second = 0;
+    }
+    // A real message takes the slot of the next dummy, keeping the observed rate flat
+    if real_message_pending {
+        record_send(scheduler, now_ms);
+        return false;
+    }
+    if scheduler.bytes_this_second + config.message_size as u64 > config.bandwidth_budget {
+        return false;
+    }
+    scheduler.bytes_this_second += config.message_size as u64;
+    record_send(scheduler, now_ms);
+    true
+}
+
+fn inter_arrival_entropy(scheduler: &CoverTrafficScheduler) -> f64 {
+    // Shannon entropy of inter-arrival times bucketed to 10ms, reported as an effectiveness metric
+    let mut buckets: HashMap<u64, usize> = HashMap::new();
+    for gap in &scheduler.inter_arrivals_ms {
+        *buckets.entry((gap / 10.0) as u64).or_default() += 1;
+    }
+    let total = scheduler.inter_arrivals_ms.len() as f64;
+    buckets.values().map(|&count| {
+        let p = count as f64 / total;
+        -p * p.log2()
+    }).sum()
+}
+
+// DAG Consensus: Stake-weighted Validator Set
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Validator {
+    peer_id: String,
+    // Staked rUv, taken from exchange staking data at the start of the epoch
+    stake: u128,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ValidatorSet {
+    epoch: u64,
+    validators: Vec<Validator>,
+}
+
+fn sample_validators(set: &ValidatorSet, sample_size: usize) -> Vec<String> {
+    // Each query round samples peers with probability proportional to stake. Zero-stake
+    // validators can never be drawn, and a drawn validator leaves the pool, so the loop ends
+    // once the pool is exhausted even if it holds fewer than sample_size peers
+    let mut candidates: Vec<&Validator> = set.validators.iter().filter(|v| v.stake > 0).collect();
+    let mut total: u128 = candidates.iter().map(|v| v.stake).sum();
+    let mut sample = Vec::new();
+    while sample.len() < sample_size && total > 0 {
+        // target < total, so the walk stops inside the candidate list
+        let mut target = rand::random::<u128>() % total;
+        let mut index = 0;
+        while target >= candidates[index].stake {
+            target -= candidates[index].stake;
+            index += 1;
+        }
+        let drawn = candidates.swap_remove(index);
+        total -= drawn.stake;
+        if !sample.contains(&drawn.peer_id) {
+            sample.push(drawn.peer_id.clone());
+        }
+    }
+    sample
+}
+
+fn rotate_validator_set(current: &ValidatorSet, stakes: &HashMap<String, u128>) -> ValidatorSet {
+    // Changes only take effect at epoch boundaries so every node samples from the same set
+    ValidatorSet {
+        epoch: current.epoch + 1,
+        validators: stakes
+            .iter()
+            .filter(|(_, &stake)| stake > 0)
+            .map(|(peer_id, &stake)| Validator { peer_id: peer_id.clone(), stake })
+            .collect(),
+    }
+}
+
+fn report_equivocation() -> Result<(), Box<dyn Error>> {
+    // Functionality to file a slashing report when a validator answers the same query with conflicting votes
+    // Include both signed responses so any node can verify the report
+    Ok(())
+}
+
+// CLI: Shell Completions and Man Pages
+// Both are generated from the ExchangeCli definition, so they never drift from the real CLI
+use clap::{Command, CommandFactory, Parser};
+use clap_complete::Shell;
+
+fn print_completions(shell: Shell) -> Result<(), Box<dyn Error>> {
+    let mut command = ExchangeCli::command();
+    let name = command.get_name().to_string();
+    clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
+    Ok(())
+}
+
+fn generate_man_pages(out_dir: Option<std::path::PathBuf>) -> Result<(), Box<dyn Error>> {
+    let command = ExchangeCli::command();
+    match out_dir {
+        None => clap_mangen::Man::new(command).render(&mut std::io::stdout())?,
+        Some(dir) => {
+            // One page per subcommand, e.g. qudag-exchange-wallet.1
+            write_man_page(&command, &dir, command.get_name())?;
+            for subcommand in command.get_subcommands() {
+                let name = format!("{}-{}", command.get_name(), subcommand.get_name());
+                write_man_page(subcommand, &dir, &name)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_man_page(command: &Command, dir: &std::path::Path, name: &str) -> Result<(), Box<dyn Error>> {
+    let mut file = std::fs::File::create(dir.join(format!("{}.1", name)))?;
+    clap_mangen::Man::new(command.clone()).render(&mut file)?;
+    Ok(())
+}
+
+// Accounting: Tax Lots and Capital Gains
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+enum LotMethod {
+    Fifo,
+    Lifo,
+    // Highest cost first, which minimizes realized gains
+    Hifo,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TaxLot {
+    acquired_at: u64,
+    quantity: Decimal,
+    unit_cost: Decimal,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct GainsReport {
+    period: String,
+    realized: Decimal,
+    unrealized: Decimal,
+}
+
+fn dispose_lots(lots: &mut Vec<TaxLot>, method: LotMethod, mut quantity: Decimal, unit_price: Decimal) -> Result<Decimal, Box<dyn Error>> {
+    // Validated up front, so a rejected disposal leaves the lots untouched
+    if quantity.is_sign_negative() {
+        return Err("disposal quantity must not be negative".into());
+    }
+    if quantity > lots.iter().map(|lot| lot.quantity).sum::<Decimal>() {
+        return Err("disposal exceeds recorded rUv holdings".into());
+    }
+    match method {
+        LotMethod::Fifo => lots.sort_by_key(|lot| lot.acquired_at),
+        LotMethod::Lifo => lots.sort_by_key(|lot| std::cmp::Reverse(lot.acquired_at)),
+        LotMethod::Hifo => lots.sort_by(|a, b| b.unit_cost.cmp(&a.unit_cost)),
+    }
+
+    let mut realized = Decimal::ZERO;
+    for lot in lots.iter_mut() {
+        if quantity.is_zero() {
+            break;
+        }
+        let used = lot.quantity.min(quantity);
+        realized += used * (unit_price - lot.unit_cost);
+        lot.quantity -= used;
+        quantity -= used;
+    }
+    lots.retain(|lot| !lot.quantity.is_zero());
+    Ok(realized)
+}
+
+fn unrealized_gains(lots: &[TaxLot], oracle_price: Decimal) -> Decimal {
+    lots.iter().map(|lot| lot.quantity * (oracle_price - lot.unit_cost)).sum()
+}
+
+fn export_gains_report(report: &GainsReport) -> Result<(), Box<dyn Error>> {
+    // Write one CSV per accounting period alongside the transaction records
+    let mut writer = csv::Writer::from_path(format!("gains-{}.csv", report.period))?;
+    writer.serialize(report)?;
+    writer.flush()?;
+    Ok(())
+}
+
+// Node: Daemon Mode
+// Completes `start --background`: PID file, rotating logs, signal handling, and a watchdog
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DaemonConfig {
+    pid_file: String,
+    log_file: String,
+    // Rotate when the log exceeds this size or age, keeping `keep` old files
+    max_log_bytes: u64,
+    max_log_age_secs: u64,
+    keep: usize,
+    restart_on_crash: bool,
+}
+
+fn write_pid_file(config: &DaemonConfig) -> Result<(), Box<dyn Error>> {
+    // Refuse to start if another live process already owns the PID file
+    if let Ok(existing) = std::fs::read_to_string(&config.pid_file) {
+        let pid: i32 = existing.trim().parse()?;
+        if std::path::Path::new(&format!("/proc/{}", pid)).exists() {
+            return Err(format!("node already running with pid {}", pid).into());
+        }
+    }
+    std::fs::write(&config.pid_file, std::process::id().to_string())?;
+    Ok(())
+}
+
+fn rotate_logs(config: &DaemonConfig) -> Result<(), Box<dyn Error>> {
+    let metadata = std::fs::metadata(&config.log_file)?;
+    let age = metadata.modified()?.elapsed()?.as_secs();
+    if metadata.len() < config.max_log_bytes && age < config.max_log_age_secs {
+        return Ok(());
+    }
+    // node.log -> node.log.1 -> node.log.2 ..., dropping the oldest
+    for i in (1..config.keep).rev() {
+        let _ = std::fs::rename(format!("{}.{}", config.log_file, i), format!("{}.{}", config.log_file, i + 1));
+    }
+    std::fs::rename(&config.log_file, format!("{}.1", config.log_file))?;
+    Ok(())
+}
+
+fn handle_daemon_signals() -> Result<(), Box<dyn Error>> {
+    // Functionality to handle signals once daemonized
+    // - SIGHUP: reload configuration and reopen log files
+    // - SIGTERM: stop accepting work, flush state, remove the PID file, and exit
+    Ok(())
+}
+
+fn run_watchdog() -> Result<(), Box<dyn Error>> {
+    // Functionality to supervise the node process when restart_on_crash is set
+    // Restart with exponential backoff, and give up after repeated crashes within a short window
+    Ok(())
+}
+
+// Exchange: Genesis Snapshots
+// `export-genesis` and `init --from-snapshot` let a network relaunch from a known state
+#[derive(Debug, Serialize, Deserialize)]
+struct GenesisSnapshot {
+    ledger: Ledger,
+    validators: ValidatorSet,
+    config: serde_json::Value,
+    // blake3 over the canonical encoding of everything above
+    state_hash: String,
+}
+
+fn snapshot_hash(ledger: &Ledger, validators: &ValidatorSet, config: &serde_json::Value) -> Result<String, Box<dyn Error>> {
+    // Every consensus-relevant field, with maps as BTreeMaps so the encoding does not depend on
+    // HashMap iteration order; serde_json length-delimits strings, so fields can't run together
+    let sorted = |map: &HashMap<AssetId, u128>| map.iter().map(|(asset, amount)| (asset.0.clone(), *amount)).collect::<BTreeMap<_, _>>();
+    let balances: BTreeMap<&String, BTreeMap<String, u128>> = ledger.balances.iter().map(|(account, b)| (account, sorted(b))).collect();
+    let limits: BTreeMap<&String, &SpendingLimit> = ledger.limits.iter().collect();
+    let nonces: BTreeMap<&String, &u64> = ledger.nonces.iter().collect();
+    let domains: BTreeMap<&String, &DarkDomain> = ledger.domains.iter().collect();
+    let escrows: BTreeMap<&String, &Escrow> = ledger.escrows.iter().collect();
+    let minted: std::collections::BTreeSet<&String> = ledger.minted_contributions.iter().collect();
+    let mut validator_list: Vec<&Validator> = validators.validators.iter().collect();
+    validator_list.sort_by(|a, b| a.peer_id.cmp(&b.peer_id));
+
+    let canonical = serde_json::to_vec(&(balances, sorted(&ledger.supply), limits, nonces, domains, escrows, &ledger.cost_model, minted, ledger.reservations_opened, validators.epoch, validator_list, config))?;
+    Ok(blake3::hash(&canonical).to_hex().to_string())
+}
+
+fn export_genesis(ledger: Ledger, validators: ValidatorSet, config: serde_json::Value, path: &str) -> Result<(), Box<dyn Error>> {
+    let state_hash = snapshot_hash(&ledger, &validators, &config)?;
+    let snapshot = GenesisSnapshot { ledger, validators, config, state_hash };
+    std::fs::write(path, serde_json::to_vec_pretty(&snapshot)?)?;
+    Ok(())
+}
+
+fn init_from_snapshot(path: &str) -> Result<GenesisSnapshot, Box<dyn Error>> {
+    let snapshot: GenesisSnapshot = serde_json::from_slice(&std::fs::read(path)?)?;
+
+    if snapshot_hash(&snapshot.ledger, &snapshot.validators, &snapshot.config)? != snapshot.state_hash {
+        return Err("snapshot state hash does not match its contents".into());
+    }
+
+    // Recorded supply must equal the sum of imported balances for every asset
+    for (asset, supply) in &snapshot.ledger.supply {
+        let imported: u128 = snapshot.ledger.balances.values().filter_map(|b| b.get(asset)).sum();
+        if imported != *supply {
+            return Err(format!("imported {} supply {} does not match snapshot supply {}", asset.0, imported, supply).into());
+        }
+    }
+
+    Ok(snapshot)
+}
+
+// MCP Transport: Server-Sent Events
+// For environments that block WebSockets
+#[derive(Debug, Default)]
+struct SseSession {
+    session_id: String,
+    next_event_id: u64,
+    // Recent events kept for Last-Event-ID resume after a reconnect
+    backlog: Vec<(u64, String)>,
+    backlog_limit: usize,
+}
+
+fn push_sse_event(session: &mut SseSession, message: &serde_json::Value) -> String {
+    session.next_event_id += 1;
+    let data = message.to_string();
+    session.backlog.push((session.next_event_id, data.clone()));
+    if session.backlog.len() > session.backlog_limit {
+        session.backlog.remove(0);
+    }
+    format!("id: {}\nevent: message\ndata: {}\n\n", session.next_event_id, data)
+}
+
+fn resume_sse_session(session: &SseSession, last_event_id: u64) -> Result<Vec<String>, Box<dyn Error>> {
+    // Replay everything after Last-Event-ID, if the backlog still holds it
+    match session.backlog.first() {
+        Some((oldest, _)) if *oldest > last_event_id + 1 => Err("events since Last-Event-ID are no longer available".into()),
+        _ => Ok(session
+            .backlog
+            .iter()
+            .filter(|(id, _)| *id > last_event_id)
+            .map(|(id, data)| format!("id: {}\nevent: message\ndata: {}\n\n", id, data))
+            .collect()),
+    }
+}
+
+fn serve_sse_transport() -> Result<(), Box<dyn Error>> {
+    // Functionality to serve GET /sse for the event stream and POST /message for client requests
+    Ok(())
+}
+
+// DAG: Parallel Traversal and Ancestor Queries
+// Ancestor checks are hot paths for conflict checking and weight calculation
+use rayon::prelude::*;
+use std::sync::Mutex;
+
+type VertexId = [u8; 32];
+
+#[derive(Debug, Default)]
+struct Dag {
+    parents: HashMap<VertexId, Vec<VertexId>>,
+    // Bloom filter of each vertex's ancestors; a miss proves "not an ancestor" without a traversal
+    ancestor_filters: HashMap<VertexId, bloomfilter::Bloom<VertexId>>,
+}
+
+fn parallel_ancestors(dag: &Dag, start: VertexId, max_depth: usize) -> HashSet<VertexId> {
+    // Bounded BFS; the visited set is sharded by first byte so workers rarely contend on a lock
+    let shards: Vec<Mutex<HashSet<VertexId>>> = (0..256).map(|_| Mutex::new(HashSet::new())).collect();
+    let mut frontier = vec![start];
+
+    for _ in 0..max_depth {
+        frontier = frontier
+            .par_iter()
+            .flat_map_iter(|vertex| dag.parents.get(vertex).into_iter().flatten())
+            .filter(|parent| shards[parent[0] as usize].lock().unwrap().insert(**parent))
+            .copied()
+            .collect();
+        if frontier.is_empty() {
+            break;
+        }
+    }
+
+    shards.into_iter().flat_map(|shard| shard.into_inner().unwrap()).collect()
+}
+
+fn is_ancestor(dag: &Dag, ancestor: VertexId, descendant: VertexId) -> bool {
+    match dag.ancestor_filters.get(&descendant) {
+        Some(filter) if !filter.check(&ancestor) => false,
+        // A filter hit may be a false positive, so confirm with a traversal
+        _ => parallel_ancestors(dag, descendant, usize::MAX).contains(&ancestor),
+    }
+}
+
+fn benchmark_dag_traversal() -> Result<(), Box<dyn Error>> {
+    // Functionality to benchmark ancestor queries on generated DAGs with 1M+ vertices
+    // Compare sequential BFS, parallel BFS, and filter-accelerated is_ancestor
+    Ok(())
+}
+
+// Provider: Job Queue with Priority Classes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+enum JobClass {
+    // Guaranteed capacity paid for up front; never preempted
+    Reserved,
+    // Best-effort; preempted when reserved capacity is claimed
+    Spot,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ProviderJob {
+    id: String,
+    class: JobClass,
+    cpu_cores: u32,
+    #[serde(default)]
+    memory_gb: u32,
+    // Paying account, for fair-share limits
+    #[serde(default)]
+    consumer: String,
+    #[serde(default)]
+    price_per_hour: u128,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ProviderStats {
+    jobs_completed: u64,
+    jobs_preempted: u64,
+    // Cores in use per class, for per-class utilization reporting
+    cores_in_use: HashMap<JobClass, u32>,
+    #[serde(default)]
+    jobs_failed: u64,
+    // Lifetime rUv earned from completed jobs
+    #[serde(default)]
+    earnings: u128,
+    // Job run time histogram; counts per JOB_LATENCY_BUCKETS upper bound, plus +Inf last
+    #[serde(default)]
+    latency_buckets: Vec<u64>,
+    #[serde(default)]
+    latency_sum_secs: f64,
+}
+
+#[derive(Debug, Default)]
+struct JobQueue {
+    queued: Vec<ProviderJob>,
+    running: Vec<ProviderJob>,
+    total_cores: u32,
+    total_memory_gb: u32,
+    policy: SchedulerPolicy,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SchedulerPolicy {
+    // Let a queued job preempt running spot jobs that pay sufficiently less
+    preempt_for_higher_price: bool,
+    // How much more the queued job must pay per core-hour, e.g. 1.5 = 50% more
+    min_price_ratio: f64,
+    // Largest fraction of total cores one consumer may hold while others are waiting
+    max_consumer_share: f64,
+}
+
+impl Default for SchedulerPolicy {
+    fn default() -> Self {
+        SchedulerPolicy { preempt_for_higher_price: false, min_price_ratio: 1.5, max_consumer_share: 1.0 }
+    }
+}
+
+fn free_capacity(queue: &JobQueue) -> (u32, u32) {
+    let cores: u32 = queue.running.iter().map(|j| j.cpu_cores).sum();
+    let memory: u32 = queue.running.iter().map(|j| j.memory_gb).sum();
+    (queue.total_cores.saturating_sub(cores), queue.total_memory_gb.saturating_sub(memory))
+}
+
+fn preempt(queue: &mut JobQueue, stats: &mut ProviderStats, position: usize) -> Result<(), Box<dyn Error>> {
+    let preempted = queue.running.remove(position);
+    checkpoint_before_preempt(&preempted)?;
+    *stats.cores_in_use.entry(preempted.class).or_default() -= preempted.cpu_cores;
+    stats.jobs_preempted += 1;
+    // Preempted spot work goes back to the queue to resume from its checkpoint
+    queue.queued.push(preempted);
+    Ok(())
+}
+
+fn start_job(queue: &mut JobQueue, stats: &mut ProviderStats, job: ProviderJob) -> Result<(), Box<dyn Error>> {
+    let fits = |queue: &JobQueue| {
+        let (cores, memory) = free_capacity(queue);
+        cores >= job.cpu_cores && memory >= job.memory_gb
+    };
+
+    // Reserved jobs reclaim capacity from spot jobs, newest first, but only if preempting every
+    // spot job would make room; otherwise nothing is killed for a job that still can't start
+    if job.class == JobClass::Reserved && !fits(queue) {
+        let (cores, memory) = queue.running.iter().filter(|j| j.class == JobClass::Spot).fold(free_capacity(queue), |(c, m), j| (c + j.cpu_cores, m + j.memory_gb));
+        if cores < job.cpu_cores || memory < job.memory_gb {
+            return Err("not enough capacity for reserved job".into());
+        }
+        while !fits(queue) {
+            let position = queue.running.iter().rposition(|j| j.class == JobClass::Spot).ok_or("not enough capacity for reserved job")?;
+            preempt(queue, stats, position)?;
+        }
+    }
+
+    if !fits(queue) {
+        queue.queued.push(job);
+        return Ok(());
+    }
+    *stats.cores_in_use.entry(job.class).or_default() += job.cpu_cores;
+    queue.running.push(job);
+    Ok(())
+}
+
+fn checkpoint_before_preempt(job: &ProviderJob) -> Result<(), Box<dyn Error>> {
+    // Functionality to give the job a chance to checkpoint before it is stopped
+    // Jobs register a hook; the provider waits for it up to a grace period
+    log_activity(&format!("checkpointing spot job {} before preemption", job.id))
+}
+
+// Exchange Market: Spot and Reserved Capacity
+// Offer classes match the provider's job classes, so a trade maps straight onto its scheduling
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ResourceOffer {
+    id: String,
+    provider: String,
+    class: JobClass,
+    #[serde(default)]
+    resource_type: String,
+    cpu_cores: u32,
+    #[serde(default)]
+    memory_gb: u32,
+    #[serde(default)]
+    region: String,
+    price_per_hour: u128,
+    // Reserved: penalty the provider pays on default. Spot: ignored
+    default_penalty: u128,
+    // Spot: notice given before capacity is revoked. Reserved: ignored
+    revocation_notice_secs: u64,
+    // Offers without a TTL stay listed until cancelled
+    #[serde(default)]
+    expires_at: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Settlement {
+    to_provider: u128,
+    to_consumer: u128,
+}
+
+fn settle_trade(offer: &ResourceOffer, paid: u128, hours_delivered: u128, hours_agreed: u128) -> Result<Settlement, Box<dyn Error>> {
+    if hours_agreed == 0 || hours_delivered > hours_agreed {
+        return Err("invalid delivery report".into());
+    }
+    let earned = paid * hours_delivered / hours_agreed;
+
+    match offer.class {
+        // Reserved capacity is guaranteed: a shortfall refunds the consumer and costs the provider its penalty
+        JobClass::Reserved if hours_delivered < hours_agreed => Ok(Settlement {
+            to_provider: earned.saturating_sub(offer.default_penalty),
+            to_consumer: paid - earned + offer.default_penalty.min(earned),
+        }),
+        // Spot capacity may be revoked with notice; the consumer pays only for what was delivered
+        _ => Ok(Settlement { to_provider: earned, to_consumer: paid - earned }),
+    }
+}
+
+// AI Memory: Shared Knowledge Base
+// Lets agents share learned facts through namespaces with per-namespace access control
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KnowledgeEntry {
+    value: serde_json::Value,
+    // Incremented on every write; writers must present the version they read
+    version: u64,
+    written_by: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Namespace {
+    readers: HashSet<String>,
+    writers: HashSet<String>,
+    entries: HashMap<String, KnowledgeEntry>,
+}
+
+fn write_knowledge(namespace: &mut Namespace, agent: &str, key: &str, value: serde_json::Value, expected_version: u64) -> Result<u64, Box<dyn Error>> {
+    if !namespace.writers.contains(agent) {
+        return Err(format!("{} may not write to this namespace", agent).into());
+    }
+
+    let current = namespace.entries.get(key).map_or(0, |entry| entry.version);
+    if current != expected_version {
+        return Err(format!("{} changed since version {}", key, expected_version).into());
+    }
+
+    let version = current + 1;
+    namespace.entries.insert(key.to_string(), KnowledgeEntry { value, version, written_by: agent.to_string() });
+    Ok(version)
+}
+
+fn recall(private: &HashMap<String, serde_json::Value>, shared: &[&Namespace], agent: &str, key: &str) -> Option<serde_json::Value> {
+    // An agent's own memory wins over shared namespaces; unreadable namespaces are skipped
+    private.get(key).cloned().or_else(|| {
+        shared
+            .iter()
+            .filter(|namespace| namespace.readers.contains(agent))
+            .find_map(|namespace| namespace.entries.get(key).map(|entry| entry.value.clone()))
+    })
+}
+
+// Network Discovery: Rendezvous for Private Swarms
+// Private swarms register with designated rendezvous peers instead of announcing on the public DHT
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum DiscoveryMode {
+    Dht,
+    Rendezvous { points: Vec<String>, namespace_key: Vec<u8> },
+    Both { points: Vec<String>, namespace_key: Vec<u8> },
+    // Zero-config discovery of peers on the local network
+    Mdns,
+    // Bootstrap list fetched over DNS-over-HTTPS and checked against the publisher's key
+    DnsOverHttps { resolver: String, domain: String, list_key: Vec<u8> },
+}
+
+fn rendezvous_namespace(namespace_key: &[u8]) -> String {
+    // Peers register under a hash of the shared key, so rendezvous points never learn the swarm name
+    blake3::keyed_hash(&blake3::hash(namespace_key).into(), b"qudag rendezvous namespace").to_hex().to_string()
+}
+
+fn discovery_proof(namespace_key: &[u8], peer_id: &str, timestamp: u64) -> Vec<u8> {
+    // Discovery queries carry a MAC over the peer and time; the rendezvous point only
+    // answers members holding the namespace key
+    let mut message = peer_id.as_bytes().to_vec();
+    message.extend_from_slice(&timestamp.to_le_bytes());
+    blake3::keyed_hash(&blake3::hash(namespace_key).into(), &message).as_bytes().to_vec()
+}
+
+// Returns bootstrap peers for the caller to dial; modes that discover through the swarm return none
+fn start_discovery(mode: &DiscoveryMode) -> Result<Vec<String>, Box<dyn Error>> {
+    match mode {
+        DiscoveryMode::Dht => {
+            // Kademlia only, as today
+        }
+        DiscoveryMode::Rendezvous { points, namespace_key } => {
+            // DHT stays off entirely: no provider records, no bootstrap queries
+            for point in points {
+                log_activity(&format!("registering at {} under {}", point, rendezvous_namespace(namespace_key)))?;
+            }
+        }
+        DiscoveryMode::Both { points, namespace_key } => {
+            for point in points {
+                log_activity(&format!("registering at {} under {}", point, rendezvous_namespace(namespace_key)))?;
+            }
+        }
+        DiscoveryMode::Mdns => {
+            // libp2p mDNS behaviour; discovered peers are dialed like bootstrap peers
+        }
+        DiscoveryMode::DnsOverHttps { resolver, domain, list_key } => {
+            return fetch_doh_bootstrap_list(resolver, domain, list_key);
+        }
+    }
+    Ok(Vec::new())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SignedBootstrapList {
+    peers: Vec<String>,
+    // Lists older than this are refused, so a replayed stale list can't pin nodes to dead peers
+    expires_at: u64,
+    signature: Vec<u8>,
+}
+
+fn fetch_doh_bootstrap_list(resolver: &str, domain: &str, list_key: &[u8]) -> Result<Vec<String>, Box<dyn Error>> {
+    // The list is published as base64 TXT records on _qudag-bootstrap.<domain>, queried
+    // with RFC 8484 JSON so it works where plain DNS is filtered
+    let url = format!("{}?name=_qudag-bootstrap.{}&type=TXT", resolver, domain);
+    let response: serde_json::Value = reqwest::blocking::Client::new().get(url).header("accept", "application/dns-json").send()?.json()?;
+    let encoded: String = response["Answer"]
+        .as_array()
+        .ok_or("no TXT records for bootstrap list")?
+        .iter()
+        .filter_map(|answer| answer["data"].as_str())
+        .map(|data| data.trim_matches('"'))
+        .collect();
+    use base64::Engine;
+    let list: SignedBootstrapList = serde_json::from_slice(&base64::engine::general_purpose::STANDARD.decode(encoded)?)?;
+
+    // The DoH resolver is not trusted; only the list publisher's signature is
+    let mut signed = serde_json::to_vec(&list.peers)?;
+    signed.extend_from_slice(&list.expires_at.to_le_bytes());
+    MlDsaPublicKey::from_bytes(list_key)?.verify(&signed, &list.signature).map_err(|_| "bootstrap list signature is invalid")?;
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_secs();
+    if list.expires_at < now {
+        return Err("bootstrap list has expired".into());
+    }
+    Ok(list.peers)
+}
+
+// Exchange: Ledger Invariant Harness
+// Reusable checks for proptest strategies and cargo-fuzz inputs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum LedgerOp {
+    Transfer { from: String, to: String, amount: u128 },
+    Mint { to: String, amount: u128 },
+    Burn { from: String, amount: u128 },
+    Fee { payer: String, amount: u128 },
+    // Re-submits an earlier transfer; must always be rejected
+    Replay { index: usize },
+    // Applies fully or not at all
+    Batch { transfers: Vec<(String, String, u128)> },
+}
+
+fn check_ledger_invariants(ops: &[LedgerOp]) -> Result<(), Box<dyn Error>> {
+    let ruv = AssetId(RUV.to_string());
+    let mut ledger = Ledger::default();
+    let mut expected_supply: u128 = 0;
+    let mut applied: Vec<usize> = Vec::new();
+    // Nonce each applied transfer was submitted with, so replays can reuse it
+    let mut used_nonces: HashMap<usize, u64> = HashMap::new();
+    let request = |ledger: &Ledger, from: &String, to: &String, amount: u128| TransferRequest {
+        from: from.clone(),
+        to: to.clone(),
+        asset: AssetId(RUV.to_string()),
+        amount,
+        nonce: next_nonce(ledger, from),
+    };
+
+    for (index, op) in ops.iter().enumerate() {
+        let result = match op {
+            LedgerOp::Transfer { from, to, amount } => {
+                let request = request(&ledger, from, to, *amount);
+                used_nonces.insert(index, request.nonce);
+                transfer(&mut ledger, &request, 0)
+            }
+            LedgerOp::Mint { to, amount } => {
+                *ledger.balances.entry(to.clone()).or_default().entry(ruv.clone()).or_default() += amount;
+                *ledger.supply.entry(ruv.clone()).or_default() += amount;
+                expected_supply += amount;
+                Ok(())
+            }
+            LedgerOp::Burn { from, amount } | LedgerOp::Fee { payer: from, amount } => {
+                let result = charge_fee(&mut ledger, from, *amount);
+                if result.is_ok() {
+                    expected_supply -= amount;
+                }
+                result
+            }
+            LedgerOp::Replay { index } => match ops.get(*index) {
+                // Re-submitting an applied transfer must be rejected by the ledger
+                Some(LedgerOp::Transfer { from, to, amount }) if applied.contains(index) => {
+                    let replay = TransferRequest { nonce: used_nonces[index], ..request(&ledger, from, to, *amount) };
+                    if transfer(&mut ledger, &replay, 0).is_ok() {
+                        return Err(format!("replay of operation {} was accepted", index).into());
+                    }
+                    Err("replay rejected".into())
+                }
+                _ => Ok(()),
+            },
+            LedgerOp::Batch { transfers } => {
+                // Consecutive nonces per sender within the batch
+                let mut next: HashMap<&String, u64> = HashMap::new();
+                let requests: Vec<TransferRequest> = transfers
+                    .iter()
+                    .map(|(from, to, amount)| {
+                        let nonce = next.entry(from).or_insert_with(|| next_nonce(&ledger, from));
+                        let request = TransferRequest { nonce: *nonce, ..request(&ledger, from, to, *amount) };
+                        *nonce += 1;
+                        request
+                    })
+                    .collect();
+                let before = ledger.balances.clone();
+                let result = transfer_batch(&mut ledger, &requests, 0);
+                if result.is_err() && !balances_equal(&before, &ledger.balances) {
+                    return Err(format!("failed batch {} left partial changes", index).into());
+                }
+                result
+            }
+        };
+        if result.is_ok() {
+            applied.push(index);
+        }
+
+        // Conservation: supply only moves with mints and burns
+        let total: u128 = ledger.balances.values().filter_map(|b| b.get(&ruv)).sum();
+        if total != expected_supply || ledger.supply.get(&ruv).copied().unwrap_or(0) != expected_supply {
+            return Err(format!("supply not conserved after operation {}", index).into());
+        }
+        // Balances are unsigned, so negatives surface as underflow panics rather than values
+    }
+
+    Ok(())
+}
+
+fn balances_equal(a: &HashMap<String, HashMap<AssetId, u128>>, b: &HashMap<String, HashMap<AssetId, u128>>) -> bool {
+    // Accounts created with a zero balance during a rolled-back batch don't count as changes
+    let nonzero = |m: &HashMap<String, HashMap<AssetId, u128>>| -> HashMap<(String, AssetId), u128> {
+        m.iter()
+            .flat_map(|(account, balances)| balances.iter().filter(|(_, v)| **v > 0).map(move |(asset, v)| ((account.clone(), asset.clone()), *v)))
+            .collect()
+    };
+    nonzero(a) == nonzero(b)
+}
+
+fn fuzz_ledger(data: &[u8]) {
+    // cargo-fuzz entry point: decode arbitrary bytes into an op sequence and check it
+    if let Ok(ops) = serde_json::from_slice::<Vec<LedgerOp>>(data) {
+        check_ledger_invariants(&ops).expect("ledger invariant violated");
+    }
+}
+
+// Orchestrator Services: Typed Descriptors and Routing
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+enum ServiceProtocol {
+    Http,
+    Grpc,
+    Mcp,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ServiceDescriptor {
+    name: String,
+    protocol: ServiceProtocol,
+    // One entry per running instance
+    endpoints: Vec<String>,
+    // JSON schema of the service's requests and responses
+    schema: serde_json::Value,
+}
+
+#[derive(Debug, Default)]
+struct ServiceRouter {
+    healthy: HashMap<String, bool>,
+    next: HashMap<String, usize>,
+}
+
+fn route_request<'a>(router: &mut ServiceRouter, service: &'a ServiceDescriptor) -> Result<&'a str, Box<dyn Error>> {
+    // Round-robin over instances, skipping any the health checker has marked down
+    let start = router.next.entry(service.name.clone()).or_default();
+    for offset in 0..service.endpoints.len() {
+        let index = (*start + offset) % service.endpoints.len();
+        let endpoint = &service.endpoints[index];
+        if router.healthy.get(endpoint).copied().unwrap_or(true) {
+            *start = index + 1;
+            return Ok(endpoint);
+        }
+    }
+    Err(format!("no healthy instance of {}", service.name).into())
+}
+
+fn create_service_client(service: &ServiceDescriptor) -> Result<(), Box<dyn Error>> {
+    // Functionality to return a ready-to-use client for a discovered service
+    // - Http: reqwest client with request/response types validated against the schema
+    // - Grpc: tonic channel over all endpoints with the same failover as route_request
+    // - Mcp: MCP client session over the service's transport
+    match service.protocol {
+        ServiceProtocol::Http | ServiceProtocol::Grpc | ServiceProtocol::Mcp => Ok(()),
+    }
+}
+
+// Tools: `qudag doctor`
+// Network health probes plus a redacted diagnostic bundle users can attach to bug reports
+#[derive(Debug, Serialize, Deserialize)]
+struct ProbeResult {
+    name: String,
+    passed: bool,
+    detail: String,
+}
+
+fn run_health_probes() -> Result<Vec<ProbeResult>, Box<dyn Error>> {
+    let mut results = Vec::new();
+
+    // Connectivity: bootstrap reachability, NAT type, port mapping, DHT query latency
+    for probe in ["bootstrap", "nat_type", "port_mapping", "dht_latency"] {
+        results.push(ProbeResult { name: probe.to_string(), passed: true, detail: String::new() });
+    }
+
+    // Clock skew against connected peers; consensus timeouts misbehave beyond a few seconds
+    results.push(ProbeResult { name: "clock_skew".to_string(), passed: true, detail: String::new() });
+
+    // Consensus sanity: recent finalization progress and no long-stalled vertices
+    results.push(ProbeResult { name: "consensus".to_string(), passed: true, detail: String::new() });
+
+    Ok(results)
+}
+
+fn redact(text: &str) -> String {
+    // Strip anything that looks like a key, token, or password before it leaves the machine
+    text.lines()
+        .map(|line| {
+            let lower = line.to_lowercase();
+            if ["key", "secret", "token", "password"].iter().any(|word| lower.contains(word)) {
+                format!("{} = <redacted>", line.split('=').next().unwrap_or("").trim())
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn write_diagnostic_bundle(config_path: &str, log_path: &str, out: &str) -> Result<(), Box<dyn Error>> {
+    let probes = run_health_probes()?;
+    let log = std::fs::read_to_string(log_path)?;
+    let lines: Vec<&str> = log.lines().collect();
+    let bundle = serde_json::json!({
+        "probes": probes,
+        "config": redact(&std::fs::read_to_string(config_path)?),
+        // Only the tail of the log; full logs are rarely needed and may be large
+        "recent_logs": redact(&lines[lines.len().saturating_sub(500)..].join("\n")),
+        "metrics": {},
+    });
+    std::fs::write(out, serde_json::to_vec_pretty(&bundle)?)?;
+    Ok(())
+}
+
+// Exchange Fee Model: What-if Simulation
+// Lets a governance proposal be checked against real or synthetic traffic before it is submitted
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct FeeModelParams {
+    base_fee: u128,
+    // Extra fee per pending transaction beyond the target, as a congestion signal
+    congestion_multiplier: u128,
+    target_mempool_size: usize,
+    // Discount in basis points per agent tier, e.g. {"verified": 2000}
+    tier_discounts: HashMap<String, u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct HistoricalTransaction {
+    tier: String,
+    mempool_size: usize,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SimulationReport {
+    projected_revenue: u128,
+    cost_per_tier: HashMap<String, u128>,
+    peak_mempool_size: usize,
+    // Transactions that arrived above target_mempool_size and paid the congestion surcharge
+    congested_transactions: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct FeeModelComparison {
+    current: SimulationReport,
+    proposed: SimulationReport,
+    // Proposed minus current cost per tier; negative means the tier pays less
+    tier_deltas: BTreeMap<String, i128>,
+}
+
+fn fee_for(params: &FeeModelParams, tier: &str, mempool_size: usize) -> u128 {
+    let congestion = mempool_size.saturating_sub(params.target_mempool_size) as u128;
+    let fee = params.base_fee.saturating_add(congestion.saturating_mul(params.congestion_multiplier));
+    // Params come from proposal files and browser clients, so a discount above 100% is capped, not trusted
+    let discount = params.tier_discounts.get(tier).copied().unwrap_or(0).min(10_000) as u128;
+    fee.saturating_sub(fee.saturating_mul(discount) / 10_000)
+}
+
+fn simulate_fee_model(params: &FeeModelParams, history: &[HistoricalTransaction]) -> SimulationReport {
+    // Replays the workload as-is; fee elasticity of demand is not modelled yet
+    let mut report = SimulationReport::default();
+    for tx in history {
+        let fee = fee_for(params, &tx.tier, tx.mempool_size);
+        report.projected_revenue += fee;
+        *report.cost_per_tier.entry(tx.tier.clone()).or_default() += fee;
+        report.peak_mempool_size = report.peak_mempool_size.max(tx.mempool_size);
+        if tx.mempool_size > params.target_mempool_size {
+            report.congested_transactions += 1;
+        }
+    }
+    report
+}
+
+fn compare_fee_models(current: &FeeModelParams, proposed: &FeeModelParams, history: &[HistoricalTransaction]) -> FeeModelComparison {
+    let current = simulate_fee_model(current, history);
+    let proposed = simulate_fee_model(proposed, history);
+    let tiers: std::collections::BTreeSet<&String> = current.cost_per_tier.keys().chain(proposed.cost_per_tier.keys()).collect();
+    let tier_deltas = tiers
+        .into_iter()
+        .map(|tier| {
+            let before = current.cost_per_tier.get(tier).copied().unwrap_or(0) as i128;
+            let after = proposed.cost_per_tier.get(tier).copied().unwrap_or(0) as i128;
+            (tier.clone(), after - before)
+        })
+        .collect();
+    FeeModelComparison { current, proposed, tier_deltas }
+}
+
+#[derive(clap::Subcommand)]
+enum FeesCommand {
+    /// Replay a transaction history under a proposed FeeModelParams and compare it with the current one
+    Simulate {
+        #[arg(long)]
+        proposal: std::path::PathBuf,
+        /// JSON array of HistoricalTransaction, recorded or synthetic
+        #[arg(long)]
+        history: std::path::PathBuf,
+        #[arg(long, value_enum, default_value = "table")]
+        format: OutputFormat,
+    },
+}
+
+fn run_fees_command(command: FeesCommand, current: &FeeModelParams) -> Result<(), Box<dyn Error>> {
+    match command {
+        FeesCommand::Simulate { proposal, history, format } => {
+            let proposed: FeeModelParams = serde_json::from_slice(&std::fs::read(proposal)?)?;
+            let history: Vec<HistoricalTransaction> = serde_json::from_slice(&std::fs::read(history)?)?;
+            let comparison = compare_fee_models(current, &proposed, &history);
+            match format {
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&comparison)?),
+                OutputFormat::Table => {
+                    println!("{:<24} {:>20} {:>20}", "", "CURRENT", "PROPOSED");
+                    println!("{:<24} {:>20} {:>20}", "revenue (rUv)", comparison.current.projected_revenue, comparison.proposed.projected_revenue);
+                    println!("{:<24} {:>20} {:>20}", "congested transactions", comparison.current.congested_transactions, comparison.proposed.congested_transactions);
+                    println!("{:<24} {:>20} {:>20}", "peak mempool size", comparison.current.peak_mempool_size, comparison.proposed.peak_mempool_size);
+                    for (tier, delta) in &comparison.tier_deltas {
+                        let before = comparison.current.cost_per_tier.get(tier).copied().unwrap_or(0);
+                        let after = comparison.proposed.cost_per_tier.get(tier).copied().unwrap_or(0);
+                        println!("{:<24} {:>20} {:>20}", format!("tier {}", tier), before, format!("{} ({:+})", after, delta));
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// Chain Adapter: Payload Codec and Typed Calls
+// Integrators get structured calls and events instead of hand-parsing raw bytes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum ExchangeCall {
+    Transfer { to: String, amount: u128 },
+    Stake { amount: u128 },
+    Payout { recipients: Vec<(String, u128)> },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum ExchangeEvent {
+    Transferred { from: String, to: String, amount: u128 },
+    Staked { account: String, amount: u128 },
+    PaidOut { recipient: String, amount: u128 },
+}
+
+// Payload layout: [version: u8][selector: u8][bincode-encoded arguments]
+const PAYLOAD_VERSION: u8 = 1;
+
+fn encode_call(call: &ExchangeCall) -> Result<Vec<u8>, Box<dyn Error>> {
+    let selector = match call {
+        ExchangeCall::Transfer { .. } => 0,
+        ExchangeCall::Stake { .. } => 1,
+        ExchangeCall::Payout { .. } => 2,
+    };
+    let mut payload = vec![PAYLOAD_VERSION, selector];
+    payload.extend(bincode::serialize(call)?);
+    Ok(payload)
+}
+
+fn decode_log(data: &[u8]) -> Result<ExchangeEvent, Box<dyn Error>> {
+    match data {
+        [PAYLOAD_VERSION, _, rest @ ..] => Ok(bincode::deserialize(rest)?),
+        [version, ..] => Err(format!("unsupported payload version {}", version).into()),
+        [] => Err("empty log entry".into()),
+    }
+}
+
+fn transfer_call(to: &str, amount: u128) -> Result<Vec<u8>, Box<dyn Error>> {
+    // Typed helper for the most common call; stake and payout follow the same shape
+    encode_call(&ExchangeCall::Transfer { to: to.to_string(), amount })
+}
+
+// Swarm: Backpressure and Load Shedding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+enum TaskPriority {
+    Low,
+    Normal,
+    High,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+enum ShedPolicy {
+    // Reject new low-priority tasks while any higher queue is saturated
+    RejectLowest,
+    // Drop the oldest queued low-priority task to make room
+    DropOldestLowest,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Backpressure {
+    queue_depth: usize,
+    estimated_wait_ms: u64,
+}
+
+#[derive(Debug, Default)]
+struct SwarmQueues {
+    queues: BTreeMap<TaskPriority, std::collections::VecDeque<String>>,
+    // Per-priority high watermark; a queue at its watermark counts as saturated
+    watermarks: HashMap<TaskPriority, usize>,
+    avg_task_ms: u64,
+}
+
+fn enqueue_task(swarm: &mut SwarmQueues, task: String, priority: TaskPriority, policy: ShedPolicy) -> Result<Backpressure, Backpressure> {
+    let saturated = |swarm: &SwarmQueues, p: &TaskPriority| {
+        swarm.queues.get(p).map_or(0, |q| q.len()) >= swarm.watermarks.get(p).copied().unwrap_or(usize::MAX)
+    };
+    let higher_saturated = swarm.queues.keys().filter(|p| **p > priority).any(|p| saturated(swarm, p));
+
+    let depth: usize = swarm.queues.values().map(|q| q.len()).sum();
+    let signal = Backpressure { queue_depth: depth, estimated_wait_ms: depth as u64 * swarm.avg_task_ms };
+
+    if saturated(swarm, &priority) || (priority == TaskPriority::Low && higher_saturated) {
+        match policy {
+            ShedPolicy::RejectLowest => return Err(signal),
+            ShedPolicy::DropOldestLowest => {
+                if swarm.queues.get_mut(&TaskPriority::Low).and_then(|q| q.pop_front()).is_none() {
+                    return Err(signal);
+                }
+            }
+        }
+    }
+
+    swarm.queues.entry(priority).or_default().push_back(task);
+    // Callers get the depth and estimated wait either way, so they can slow down before being rejected
+    Ok(signal)
+}
+
+// Exchange: Canonical Ordering Within a Consensus Round
+// Every node must apply transactions finalized in the same round in the same order
+fn transaction_hash(tx: &ExchangeTransaction) -> Result<[u8; 32], Box<dyn Error>> {
+    Ok(*blake3::hash(&serde_json::to_vec(tx)?).as_bytes())
+}
+
+fn apply_round(ledger: &mut Ledger, round: Vec<(VertexId, ExchangeTransaction)>, now: u64) -> Result<Vec<(String, TransactionStatus)>, Box<dyn Error>> {
+    // Canonical order: by vertex hash, then by transaction hash; arrival order never matters
+    let mut ordered = round
+        .into_iter()
+        .map(|(vertex, tx)| Ok((vertex, transaction_hash(&tx)?, tx)))
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+    ordered.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+
+    // A failed transaction is rejected without aborting the rest of the round
+    let mut statuses = Vec::new();
+    for (_, _, tx) in ordered {
+        let status = match apply_transaction(ledger, &tx, now) {
+            Ok(()) => TransactionStatus::Finalized,
+            Err(e) => TransactionStatus::Rejected(e.to_string()),
+        };
+        statuses.push((tx.id, status));
+    }
+    Ok(statuses)
+}
+
+fn check_ordering_conformance() -> Result<(), Box<dyn Error>> {
+    // Functionality for the testkit: feed the same round to several nodes in shuffled
+    // arrival orders and require identical ledger snapshots (compare with snapshot_hash)
+    Ok(())
+}
+
+// AI Tools: HTTP Fetch with Allowlists
+// Backs the `web_search` entry in the default tool list; runs inside the sandboxed tool executor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FetchPolicy {
+    allowed_domains: Vec<String>,
+    denied_domains: Vec<String>,
+    max_response_bytes: usize,
+    allowed_content_types: Vec<String>,
+}
+
+fn domain_allowed(policy: &FetchPolicy, host: &str) -> bool {
+    let matches = |domain: &String| host == domain || host.ends_with(&format!(".{}", domain));
+    // Deny wins over allow; an empty allowlist permits nothing
+    !policy.denied_domains.iter().any(matches) && policy.allowed_domains.iter().any(matches)
+}
+
+fn fetch_url(policy: &FetchPolicy, cache: &mut HashMap<String, String>, agent: &str, url: &str) -> Result<String, Box<dyn Error>> {
+    let parsed = url::Url::parse(url)?;
+    let host = parsed.host_str().ok_or("url has no host")?;
+    if !domain_allowed(policy, host) {
+        log_activity(&format!("{} denied fetch of {}", agent, url))?;
+        return Err(format!("{} is not on the allowlist", host).into());
+    }
+
+    if let Some(text) = cache.get(url) {
+        return Ok(text.clone());
+    }
+
+    // Redirects are followed only to hosts the policy also allows; a bare get() would follow
+    // an allowlisted host's redirect anywhere
+    let redirect_policy = policy.clone();
+    let client = reqwest::blocking::Client::builder()
+        .redirect(reqwest::redirect::Policy::custom(move |attempt| {
+            let allowed = attempt.url().host_str().map_or(false, |host| domain_allowed(&redirect_policy, host));
+            if !allowed {
+                let denied = format!("redirect to {} is not on the allowlist", attempt.url());
+                attempt.error(denied)
+            } else if attempt.previous().len() >= 10 {
+                attempt.error("too many redirects")
+            } else {
+                attempt.follow()
+            }
+        }))
+        .build()?;
+    let response = client.get(parsed.as_str()).send()?;
+    let content_type = response.headers().get("content-type").and_then(|v| v.to_str().ok()).unwrap_or("").to_string();
+    if !policy.allowed_content_types.iter().any(|allowed| content_type.starts_with(allowed)) {
+        return Err(format!("content type {} is not allowed", content_type).into());
+    }
+    // Check the declared length first so oversized bodies aren't downloaded at all
+    if response.content_length().map_or(false, |len| len as usize > policy.max_response_bytes) {
+        return Err("response exceeds size limit".into());
+    }
+    // Bodies without a Content-Length are read at most one byte past the limit
+    use std::io::Read;
+    let mut body = Vec::new();
+    response.take(policy.max_response_bytes as u64 + 1).read_to_end(&mut body)?;
+    if body.len() > policy.max_response_bytes {
+        return Err("response exceeds size limit".into());
+    }
+
+    // Agents get readable text, not markup
+    let text = html2text::from_read(&body[..], 100);
+    cache.insert(url.to_string(), text.clone());
+    log_activity(&format!("{} fetched {} ({} bytes)", agent, url, body.len()))?;
+    Ok(text)
+}
+
+// Network NAT Traversal: Relay Fallback
+// When hole punching fails the connection falls back to a relay instead of silently dying
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum ConnectionPath {
+    Direct,
+    Relayed { relay: String },
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RelayUsage {
+    // Bytes forwarded per relay, settled in rUv at the end of each accounting period
+    bytes: HashMap<String, u64>,
+}
+
+fn connect_with_fallback(peer: &str, relays: &[String]) -> Result<ConnectionPath, Box<dyn Error>> {
+    if hole_punch(peer).is_ok() {
+        return Ok(ConnectionPath::Direct);
+    }
+    // Try relays in order (libp2p circuit relay first, then TURN)
+    for relay in relays {
+        if reserve_relay_slot(relay, peer).is_ok() {
+            return Ok(ConnectionPath::Relayed { relay: relay.clone() });
+        }
+    }
+    Err(format!("no direct or relayed path to {}", peer).into())
+}
+
+fn hole_punch(_peer: &str) -> Result<(), Box<dyn Error>> {
+    // Functionality to attempt a DCUtR/STUN-assisted direct connection
+    Err("hole punching not attempted".into())
+}
+
+fn reserve_relay_slot(_relay: &str, _peer: &str) -> Result<(), Box<dyn Error>> {
+    // Functionality to reserve a circuit on the relay for this peer
+    Ok(())
+}
+
+fn record_relay_bytes(usage: &mut RelayUsage, relay: &str, bytes: u64) {
+    *usage.bytes.entry(relay.to_string()).or_default() += bytes;
+}
+
+fn relay_compensation(usage: &RelayUsage, ruv_per_gib: u128) -> HashMap<String, u128> {
+    usage.bytes.iter().map(|(relay, bytes)| (relay.clone(), *bytes as u128 * ruv_per_gib / (1 << 30))).collect()
+}
+
+fn retry_direct_upgrade() -> Result<(), Box<dyn Error>> {
+    // Functionality to periodically hand relayed connections to the ConnectionUpgradeManager
+    // and swap to the direct path once a hole punch succeeds
+    Ok(())
+}
+
+// Exchange: Consistency Checker
+// Periodically compares state across peers and flags the node that disagrees with the quorum
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct NodeState {
+    node: String,
+    state_root: String,
+    // Account balance snapshot and the sequence number of the last applied transaction
+    balances: HashMap<String, u128>,
+    sequence: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Divergence {
+    node: String,
+    // First transaction sequence number where this node's history departs from the quorum
+    first_diverging_sequence: u64,
+    accounts: Vec<String>,
+}
+
+// `diverged_at` bisects a node's transaction log against a quorum node to find the first bad sequence
+fn check_consistency(states: &[NodeState], diverged_at: impl Fn(&str) -> u64) -> Vec<Divergence> {
+    // The quorum root is the one reported by the most nodes
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for state in states {
+        *counts.entry(&state.state_root).or_default() += 1;
+    }
+    let Some((quorum_root, _)) = counts.iter().max_by_key(|(_, count)| **count) else {
+        return Vec::new();
+    };
+    let Some(reference) = states.iter().find(|s| s.state_root == *quorum_root) else {
+        return Vec::new();
+    };
+
+    states
+        .iter()
+        .filter(|state| state.state_root != *quorum_root)
+        .map(|state| Divergence {
+            node: state.node.clone(),
+            first_diverging_sequence: diverged_at(&state.node),
+            accounts: reference
+                .balances
+                .iter()
+                .filter(|(account, balance)| state.balances.get(*account) != Some(balance))
+                .map(|(account, _)| account.clone())
+                .collect(),
+        })
+        .collect()
+}
+
+fn quarantine_node(divergence: &Divergence) -> Result<(), Box<dyn Error>> {
+    // Functionality to stop serving reads and votes from a node that disagrees with the quorum
+    // Optional: operators can run the checker in report-only mode
+    log_activity(&format!("quarantining {}: diverged at sequence {}", divergence.node, divergence.first_diverging_sequence))
+}
+
+// Economy: Grants Pool with Milestone Payouts
+// An inflation-funded pool pays approved proposals as reviewers attest each milestone
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Milestone {
+    description: String,
+    amount: u128,
+    deadline: u64,
+    attestations: HashSet<String>,
+    paid: bool,
+    expired: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Grant {
+    proposal_id: String,
+    recipient: String,
+    reviewers: Vec<String>,
+    required_attestations: usize,
+    milestones: Vec<Milestone>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct GrantsPool {
+    balance: u128,
+    // Amount committed to approved grants but not yet paid out
+    committed: u128,
+}
+
+fn fund_grants_pool(pool: &mut GrantsPool, epoch_inflation: u128, grants_share_bps: u128) {
+    // A governance-set share of each epoch's newly minted rUv flows into the pool
+    pool.balance += epoch_inflation * grants_share_bps / 10_000;
+}
+
+fn approve_grant(pool: &mut GrantsPool, grant: &Grant) -> Result<(), Box<dyn Error>> {
+    // Approval commits the full grant, so approved grants can never outspend the pool
+    let total = grant.milestones.iter().try_fold(0u128, |sum, m| sum.checked_add(m.amount)).ok_or("grant total overflows")?;
+    let available = pool.balance.checked_sub(pool.committed).ok_or("pool commitments exceed its balance")?;
+    if total > available {
+        return Err(format!("grant {} needs {} rUv, only {} uncommitted", grant.proposal_id, total, available).into());
+    }
+    pool.committed += total;
+    Ok(())
+}
+
+fn ruv_decimal(amount: u128) -> Result<Decimal, Box<dyn Error>> {
+    Ok(Decimal::try_from_i128_with_scale(i128::try_from(amount)?, 0)?)
+}
+
+fn attest_milestone(grant: &mut Grant, pool: &mut GrantsPool, index: usize, reviewer: &str, now: u64) -> Result<Option<u128>, Box<dyn Error>> {
+    if !grant.reviewers.iter().any(|r| r == reviewer) {
+        return Err(format!("{} is not a reviewer for {}", reviewer, grant.proposal_id).into());
+    }
+    let milestone = grant.milestones.get_mut(index).ok_or("no such milestone")?;
+    if milestone.paid || milestone.expired || now > milestone.deadline {
+        return Err("milestone is closed".into());
+    }
+
+    milestone.attestations.insert(reviewer.to_string());
+    if milestone.attestations.len() < grant.required_attestations {
+        return Ok(None);
+    }
+
+    // Enough attestations: release the payment automatically
+    let balance = pool.balance.checked_sub(milestone.amount).ok_or("grants pool balance underflow")?;
+    let committed = pool.committed.checked_sub(milestone.amount).ok_or("grants pool commitment underflow")?;
+    let amount = ruv_decimal(milestone.amount)?;
+    milestone.paid = true;
+    pool.balance = balance;
+    pool.committed = committed;
+    record_transaction(amount, &format!("grant {} milestone {} to {}", grant.proposal_id, index, grant.recipient))?;
+    Ok(Some(milestone.amount))
+}
+
+fn expire_milestones(grant: &mut Grant, pool: &mut GrantsPool, now: u64) -> Result<(), Box<dyn Error>> {
+    // Unpaid milestones past their deadline release their commitment back to the pool
+    for milestone in grant.milestones.iter_mut().filter(|m| !m.paid && !m.expired && now > m.deadline) {
+        pool.committed = pool.committed.checked_sub(milestone.amount).ok_or("grants pool commitment underflow")?;
+        milestone.expired = true;
+        record_transaction(Decimal::ZERO, &format!("grant {} milestone expired, {} rUv returned", grant.proposal_id, milestone.amount))?;
+    }
+    Ok(())
+}
+
+// Trainer: Differential Privacy for Gradient Sharing
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct DpConfig {
+    // Per-round L2 clipping bound for each gradient
+    clip_norm: f64,
+    noise_multiplier: f64,
+    // Budget set through governance rules; training stops once it would be exceeded
+    target_epsilon: f64,
+    delta: f64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PrivacyAccountant {
+    rounds: u64,
+    // Reported in coordinator metadata after every round
+    epsilon_spent: f64,
+    #[serde(default)]
+    delta_spent: f64,
+}
+
+fn privatize_gradient(gradient: &mut [f64], config: &DpConfig) -> Result<(), Box<dyn Error>> {
+    // Clip to the L2 bound so a single participant's influence is limited
+    let norm = gradient.iter().map(|g| g * g).sum::<f64>().sqrt();
+    if norm > config.clip_norm {
+        let scale = config.clip_norm / norm;
+        gradient.iter_mut().for_each(|g| *g *= scale);
+    }
+
+    // Then add Gaussian noise calibrated to the clipping bound
+    // A negative or non-finite multiplier comes from a bad config, not from the gradient
+    let normal = rand_distr::Normal::new(0.0, config.noise_multiplier * config.clip_norm).map_err(|e| format!("invalid DP noise parameters: {}", e))?;
+    let mut rng = rand::thread_rng();
+    gradient.iter_mut().for_each(|g| *g += rand_distr::Distribution::sample(&normal, &mut rng));
+    Ok(())
+}
+
+fn account_round(accountant: &mut PrivacyAccountant, config: &DpConfig) -> Result<(), Box<dyn Error>> {
+    // Advanced composition over k rounds of the Gaussian mechanism; an RDP accountant would be
+    // tighter. Half of delta is the composition slack δ', the other half is split across rounds
+    let k = (accountant.rounds + 1) as f64;
+    let delta_slack = config.delta / 2.0;
+    let delta_round = config.delta / (2.0 * k);
+    let per_round = (2.0 * (1.25 / delta_round).ln()).sqrt() / config.noise_multiplier;
+    // ε' = ε√(2k ln(1/δ')) + kε(e^ε − 1), with total δ = kδ + δ'
+    let epsilon = per_round * (2.0 * k * (1.0 / delta_slack).ln()).sqrt() + k * per_round * per_round.exp_m1();
+    let delta = k * delta_round + delta_slack;
+
+    if !epsilon.is_finite() || epsilon > config.target_epsilon {
+        return Err(format!("privacy budget exhausted: epsilon {:.3} exceeds {:.3}", epsilon, config.target_epsilon).into());
+    }
+    accountant.rounds += 1;
+    accountant.epsilon_spent = epsilon;
+    accountant.delta_spent = delta;
+    Ok(())
+}
+
+// Protocol Handshake: Cipher-suite Policy and Downgrade Protection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+enum SecurityLevel {
+    MlKem512,
+    MlKem768,
+    MlKem1024,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct HandshakeConfig {
+    // Peers offering only suites below this level are rejected
+    minimum_level: SecurityLevel,
+    offered: Vec<SecurityLevel>,
+}
+
+#[derive(Debug)]
+enum HandshakeError {
+    PolicyNotMet { offered: Vec<SecurityLevel>, minimum: SecurityLevel },
+    TranscriptMismatch,
+}
+
+impl std::fmt::Display for HandshakeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            HandshakeError::PolicyNotMet { offered, minimum } => write!(f, "peer offered {:?}, policy requires at least {:?}", offered, minimum),
+            HandshakeError::TranscriptMismatch => write!(f, "handshake transcript mismatch, possible downgrade"),
+        }
+    }
+}
+
+impl Error for HandshakeError {}
+
+fn negotiate_suite(config: &HandshakeConfig, peer_offered: &[SecurityLevel]) -> Result<SecurityLevel, HandshakeError> {
+    // Pick the strongest suite both sides offer; anything under the minimum is not negotiable
+    config
+        .offered
+        .iter()
+        .filter(|level| peer_offered.contains(level) && **level >= config.minimum_level)
+        .max()
+        .copied()
+        .ok_or_else(|| HandshakeError::PolicyNotMet { offered: peer_offered.to_vec(), minimum: config.minimum_level })
+}
+
+fn transcript_hash(initiator_offer: &[SecurityLevel], responder_offer: &[SecurityLevel], chosen: SecurityLevel) -> [u8; 32] {
+    // Both offers and the choice are bound into the key schedule, so a MITM stripping
+    // suites from either offer produces different transcripts on each side. Offers are always
+    // hashed initiator first, so both ends compute the same bytes
+    let code = |level: &SecurityLevel| match level {
+        SecurityLevel::MlKem512 => 1u8,
+        SecurityLevel::MlKem768 => 2,
+        SecurityLevel::MlKem1024 => 3,
+    };
+    let mut hasher = blake3::Hasher::new_derive_key("qudag handshake transcript v1");
+    for offer in [initiator_offer, responder_offer] {
+        hasher.update(&(offer.len() as u32).to_le_bytes());
+        hasher.update(&offer.iter().map(code).collect::<Vec<u8>>());
+    }
+    hasher.update(&[code(&chosen)]);
+    *hasher.finalize().as_bytes()
+}
+
+fn verify_transcript(ours: [u8; 32], theirs: [u8; 32]) -> Result<(), HandshakeError> {
+    // On failure the peer is dropped and the typed error is recorded in its PeerMetadata
+    if ours == theirs {
+        Ok(())
+    } else {
+        Err(HandshakeError::TranscriptMismatch)
+    }
+}
+
+// Exchange WASM: BigInt-safe Balances
+// JS numbers lose precision above 2^53, so amounts cross the WASM boundary as BigInt or decimal strings
+#[wasm_bindgen]
+pub fn balance_of(ledger_json: &str, account: &str, asset: &str) -> Result<js_sys::BigInt, JsValue> {
+    let ledger: Ledger = serde_json::from_str(ledger_json).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let amount = ledger
+        .balances
+        .get(account)
+        .and_then(|balances| balances.get(&AssetId(asset.to_string())))
+        .copied()
+        .unwrap_or(0);
+    // BigInt::from(u128) isn't available, so go through the decimal string
+    js_sys::BigInt::new(&JsValue::from_str(&amount.to_string()))
+        .map_err(|_| JsValue::from_str("amount does not fit in a BigInt"))
+}
+
+#[wasm_bindgen]
+pub fn parse_amount(amount: js_sys::BigInt) -> Result<String, JsValue> {
+    // Accept BigInt from JS and hand back the canonical decimal string used in transactions
+    let text: String = amount.to_string(10).map_err(|_| JsValue::from_str("invalid amount"))?.into();
+    text.parse::<u128>().map(|value| value.to_string()).map_err(|_| JsValue::from_str("amount must be a non-negative u128"))
+}
+
+#[wasm_bindgen]
+pub fn format_amount(amount: &str, decimals: u32, precision: u32) -> Result<String, JsValue> {
+    // "1234500" with 6 decimals and precision 2 -> "1.23"
+    let value: u128 = amount.parse().map_err(|_| JsValue::from_str("amount must be a decimal integer string"))?;
+    // 10^39 and above overflow u128
+    let unit = 10u128.checked_pow(decimals).ok_or_else(|| JsValue::from_str("decimals must be at most 38"))?;
+    let fraction = format!("{:0width$}", value % unit, width = decimals as usize);
+    let shown = &fraction[..precision.min(decimals) as usize];
+    if shown.is_empty() {
+        Ok((value / unit).to_string())
+    } else {
+        Ok(format!("{}.{}", value / unit, shown))
+    }
+}
+
+// MCP: Agent Sandbox Profiles as Resources
+// Operators can read and tighten an agent's effective permissions without restarting it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SandboxProfile {
+    agent_id: String,
+    allowed_tools: HashSet<String>,
+    // Remaining rUv the agent may spend in the current budget period
+    budget: u128,
+    rules: Vec<Rule>,
+}
+
+fn profile_resource(profile: &SandboxProfile) -> Result<serde_json::Value, Box<dyn Error>> {
+    // Exposed through resources/list and resources/read as daa://agents/{id}/sandbox
+    Ok(serde_json::json!({
+        "uri": format!("daa://agents/{}/sandbox", profile.agent_id),
+        "mimeType": "application/json",
+        "text": serde_json::to_string(profile)?,
+    }))
+}
+
+fn update_sandbox_profile(profiles: &mut HashMap<String, SandboxProfile>, updated: SandboxProfile) -> Result<(), Box<dyn Error>> {
+    // Backs the `update_sandbox_profile` MCP tool; rejected edits leave the old profile in force
+    let mut rule_ids = HashSet::new();
+    for rule in &updated.rules {
+        if rule.conditions.is_empty() {
+            return Err(format!("rule {} needs at least one condition", rule.id).into());
+        }
+        // The rule index is keyed by id, so a duplicate would shadow another rule
+        if !rule_ids.insert(rule.id) {
+            return Err(format!("duplicate rule id {}", rule.id).into());
+        }
+        let fields_named = rule.conditions.iter().all(|condition| match condition {
+            Condition::Equals { field, .. } | Condition::GreaterThan { field, .. } | Condition::LessThan { field, .. } => !field.is_empty(),
+        });
+        if !fields_named {
+            return Err(format!("rule {} has a condition without a field", rule.id).into());
+        }
+    }
+
+    log_activity(&format!("sandbox profile for {} updated", updated.agent_id))?;
+    // Enforcement reads the profile on every tool call, so the change applies immediately;
+    // subscribers get notifications/resources/updated for the profile's uri
+    profiles.insert(updated.agent_id.clone(), updated);
+    Ok(())
+}
+
+// DAG: Batched Vertex Insertion
+// Validates parents for a whole batch and applies it in one write pass instead of locking per vertex
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct NewVertex {
+    id: VertexId,
+    parents: Vec<VertexId>,
+}
+
+fn insert_batch(dag: &mut Dag, batch: Vec<NewVertex>) -> Result<Vec<VertexId>, Box<dyn Error>> {
+    // Parents may be existing vertices or other vertices in the same batch
+    let mut in_batch: HashSet<VertexId> = HashSet::new();
+    for vertex in &batch {
+        if dag.parents.contains_key(&vertex.id) || !in_batch.insert(vertex.id) {
+            return Err(format!("vertex {:x?} already exists", &vertex.id[..4]).into());
+        }
+    }
+    for vertex in &batch {
+        if vertex.parents.contains(&vertex.id) {
+            return Err(format!("vertex {:x?} lists itself as a parent", &vertex.id[..4]).into());
+        }
+        if let Some(missing) = vertex.parents.iter().find(|p| !dag.parents.contains_key(*p) && !in_batch.contains(*p)) {
+            return Err(format!("missing parent {:x?}", &missing[..4]).into());
+        }
+    }
+
+    // Existing vertices can't point into the batch, so any cycle lies within it: a topological
+    // sort over the in-batch edges must reach every vertex
+    let mut waiting: HashMap<VertexId, usize> = batch.iter().map(|v| (v.id, v.parents.iter().filter(|p| in_batch.contains(*p)).collect::<HashSet<_>>().len())).collect();
+    let mut ready: Vec<VertexId> = waiting.iter().filter(|(_, n)| **n == 0).map(|(id, _)| *id).collect();
+    let mut sorted = 0;
+    while let Some(id) = ready.pop() {
+        sorted += 1;
+        for child in batch.iter().filter(|v| v.parents.contains(&id)) {
+            let n = waiting.get_mut(&child.id).expect("every batch vertex is counted");
+            *n -= 1;
+            if *n == 0 {
+                ready.push(child.id);
+            }
+        }
+    }
+    if sorted < batch.len() {
+        return Err("batch contains a parent cycle".into());
+    }
+
+    // Single write pass; a failed batch changed nothing
+    let inserted: Vec<VertexId> = batch.iter().map(|v| v.id).collect();
+    for vertex in batch {
+        dag.parents.insert(vertex.id, vertex.parents);
+    }
+
+    Ok(inserted)
+}
+
+fn pipeline_insertion() -> Result<(), Box<dyn Error>> {
+    // Functionality to overlap the stages across batches:
+    // 1. validate batch N+1 (parents, signatures) while
+    // 2. batch N is being written and
+    // 3. consensus state for batch N-1 is being updated
+    // Benchmark target: 50k+ vertex inserts/sec on commodity hardware
+    Ok(())
+}
+
+// Exchange: Ledger-level Spending Limits
+// Caps are denominated in rUv and enforced in apply_transaction, not just by clients
+const LIMIT_WINDOW_SECS: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct SpendingLimit {
+    per_tx: Option<u128>,
+    daily: Option<u128>,
+    // (timestamp, amount) of rUv spends inside the rolling 24h window
+    recent: std::collections::VecDeque<(u64, u128)>,
+    // A requested change and the time it takes effect
+    pending: Option<(u64, Option<u128>, Option<u128>)>,
+}
+
+fn enforce_spending_limit(limit: &mut SpendingLimit, tx: &ExchangeTransaction, now: u64) -> Result<(), Box<dyn Error>> {
+    if let Some((effective_at, per_tx, daily)) = limit.pending {
+        if now >= effective_at {
+            limit.per_tx = per_tx;
+            limit.daily = daily;
+            limit.pending = None;
+        }
+    }
+    if tx.asset.0 != RUV {
+        return Ok(());
+    }
+
+    if limit.per_tx.map_or(false, |cap| tx.amount > cap) {
+        return Err(ExchangeError::new(ErrorCode::LimitExceeded, format!("transaction {} exceeds the per-transaction limit", tx.id)).into());
+    }
+
+    while limit.recent.front().map_or(false, |(at, _)| *at + LIMIT_WINDOW_SECS <= now) {
+        limit.recent.pop_front();
+    }
+    let spent: u128 = limit.recent.iter().map(|(_, amount)| amount).sum();
+    if limit.daily.map_or(false, |cap| spent + tx.amount > cap) {
+        return Err(ExchangeError::new(ErrorCode::LimitExceeded, format!("transaction {} exceeds the daily limit", tx.id)).into());
+    }
+
+    Ok(())
+}
+
+fn request_limit_change(limit: &mut SpendingLimit, per_tx: Option<u128>, daily: Option<u128>, now: u64, delay_secs: u64) {
+    // Tightening applies at once; loosening waits out the delay so a stolen key can't lift the cap
+    let tighter = |new: Option<u128>, old: Option<u128>| match (new, old) {
+        (Some(new), Some(old)) => new <= old,
+        (Some(_), None) => true,
+        (None, old) => old.is_none(),
+    };
+    if tighter(per_tx, limit.per_tx) && tighter(daily, limit.daily) {
+        limit.per_tx = per_tx;
+        limit.daily = daily;
+        limit.pending = None;
+    } else {
+        limit.pending = Some((now + delay_secs, per_tx, daily));
+    }
+}
+
+// Orchestrator: Per-workflow Cost Attribution
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+enum CostSource {
+    ChainFees,
+    AiTokens,
+    Compute,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CostRecord {
+    workflow_id: String,
+    execution_id: String,
+    source: CostSource,
+    // Normalized to rUv at the time the cost was incurred
+    amount: u128,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct WorkflowCostReport {
+    workflow_id: String,
+    total: u128,
+    by_source: HashMap<CostSource, u128>,
+    by_execution: HashMap<String, u128>,
+}
+
+fn collect_cost(records: &mut Vec<CostRecord>, workflow_id: &str, execution_id: &str, source: CostSource, amount: u128) {
+    // Called by the chain, AI and compute integrations whenever they spend on a workflow's behalf
+    records.push(CostRecord { workflow_id: workflow_id.to_string(), execution_id: execution_id.to_string(), source, amount });
+}
+
+fn workflow_cost_report(records: &[CostRecord], workflow_id: &str) -> WorkflowCostReport {
+    // Served from GET /workflows/{id}/costs
+    let mut report = WorkflowCostReport { workflow_id: workflow_id.to_string(), ..Default::default() };
+    for record in records.iter().filter(|r| r.workflow_id == workflow_id) {
+        report.total += record.amount;
+        *report.by_source.entry(record.source).or_default() += record.amount;
+        *report.by_execution.entry(record.execution_id.clone()).or_default() += record.amount;
+    }
+    report
+}
+
+fn bill_initiating_agent(ledger: &mut Ledger, report: &WorkflowCostReport, agent_account: &str, treasury: &str) -> Result<(), Box<dyn Error>> {
+    // Optional: charge the agent that started the workflow from its exchange account
+    transfer_asset(ledger, agent_account, treasury, &AssetId(RUV.to_string()), report.total)
+}
+
+// Network Shadow Addresses: Usage-based Rotation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RotationPolicies {
+    max_age_secs: Option<u64>,
+    // Rotate after this many bytes or messages have been received on the address
+    max_bytes: Option<u64>,
+    max_messages: Option<u64>,
+    // Use a separate address for each counterparty; the address list is then kept per counterparty
+    // and a new counterparty always triggers a fresh address
+    per_counterparty: bool,
+    // How long a retired address keeps receiving after handover
+    grace_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ShadowAddress {
+    address: String,
+    created_at: u64,
+    bytes: u64,
+    messages: u64,
+    retired_at: Option<u64>,
+}
+
+fn needs_rotation(policies: &RotationPolicies, address: &ShadowAddress, now: u64) -> bool {
+    // A clock that is behind the address's creation time means no age yet, not an underflow
+    policies.max_age_secs.map_or(false, |max| now.checked_sub(address.created_at).map_or(false, |age| age >= max))
+        || policies.max_bytes.map_or(false, |max| address.bytes >= max)
+        || policies.max_messages.map_or(false, |max| address.messages >= max)
+}
+
+fn rotate_address(addresses: &mut Vec<ShadowAddress>, new_address: String, notify: impl Fn(&str, &str), now: u64) {
+    // Handover is atomic: the new address is live before the old one is retired,
+    // and the old one still receives during the grace window
+    addresses.push(ShadowAddress { address: new_address.clone(), created_at: now, bytes: 0, messages: 0, retired_at: None });
+    if let Some(old) = addresses.iter_mut().rev().skip(1).find(|a| a.retired_at.is_none()) {
+        old.retired_at = Some(now);
+        notify(&old.address, &new_address);
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ShadowAddressBook {
+    shared: Vec<ShadowAddress>,
+    // Only used with per_counterparty; each counterparty rotates independently
+    by_counterparty: HashMap<String, Vec<ShadowAddress>>,
+}
+
+fn address_for(policies: &RotationPolicies, book: &mut ShadowAddressBook, counterparty: &str, generate: impl FnOnce() -> String, notify: impl Fn(&str, &str), now: u64) -> String {
+    let addresses = if policies.per_counterparty { book.by_counterparty.entry(counterparty.to_string()).or_default() } else { &mut book.shared };
+    // A counterparty seen for the first time has an empty list, so it always gets a fresh address
+    let live = addresses.iter().rev().find(|a| a.retired_at.is_none());
+    if live.map_or(true, |address| needs_rotation(policies, address, now)) {
+        rotate_address(addresses, generate(), notify, now);
+    }
+    addresses.last().expect("rotated above if empty").address.clone()
+}
+
+fn accepts_traffic(policies: &RotationPolicies, address: &ShadowAddress, now: u64) -> bool {
+    address.retired_at.map_or(true, |retired| now < retired.saturating_add(policies.grace_secs))
+}
+
+// Exchange: Standard Error Codes
+// One code per failure class, shared by the API server, CLI exit codes, and WASM errors,
+// so integrators can branch on the code instead of parsing messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum ErrorCode {
+    InsufficientBalance,
+    BadNonce,
+    Expired,
+    Frozen,
+    LimitExceeded,
+    InvalidSignature,
+    ConsensusTimeout,
+    Unauthorized,
+    RateLimited,
+    // Well-formed request, but the transaction breaks a ledger rule such as the memo size
+    InvalidTransaction,
+    Internal,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ExchangeError {
+    code: ErrorCode,
+    message: String,
+}
+
+impl ExchangeError {
+    fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        ExchangeError { code, message: message.into() }
+    }
+}
+
+impl std::fmt::Display for ExchangeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?}: {}", self.code, self.message)
+    }
+}
+
+impl Error for ExchangeError {}
+
+fn error_code_of(error: &(dyn Error + 'static)) -> ErrorCode {
+    // Errors that never went through ExchangeError are reported as internal
+    error.downcast_ref::<ExchangeError>().map_or(ErrorCode::Internal, |e| e.code)
+}
+
+impl ErrorCode {
+    fn http_status(self) -> u16 {
+        match self {
+            ErrorCode::InsufficientBalance | ErrorCode::LimitExceeded => 402,
+            ErrorCode::BadNonce | ErrorCode::InvalidSignature | ErrorCode::InvalidTransaction => 400,
+            ErrorCode::Expired => 410,
+            ErrorCode::Frozen => 403,
+            ErrorCode::ConsensusTimeout => 504,
+            ErrorCode::Unauthorized => 401,
+            ErrorCode::RateLimited => 429,
+            ErrorCode::Internal => 500,
+        }
+    }
+
+    fn exit_code(self) -> i32 {
+        // Stable CLI exit codes; 1 stays reserved for usage errors
+        match self {
+            ErrorCode::InsufficientBalance => 10,
+            ErrorCode::BadNonce => 11,
+            ErrorCode::Expired => 12,
+            ErrorCode::Frozen => 13,
+            ErrorCode::LimitExceeded => 14,
+            ErrorCode::InvalidSignature => 15,
+            ErrorCode::ConsensusTimeout => 16,
+            ErrorCode::Unauthorized => 17,
+            ErrorCode::RateLimited => 18,
+            ErrorCode::InvalidTransaction => 19,
+            ErrorCode::Internal => 2,
+        }
+    }
+}
+
+fn error_to_js(error: &ExchangeError) -> JsValue {
+    // WASM callers get `{ code, message }` objects rather than bare strings
+    serde_wasm_bindgen::to_value(error).unwrap_or_else(|_| JsValue::from_str(&error.message))
+}
+
+// AI Claude Module: Retry, Backoff, and Failover
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RetryConfig {
+    max_retries: u32,
+    base_delay_ms: u64,
+    max_delay_ms: u64,
+    // Secondary provider/model from SpawnConfig, used when the primary's circuit is open
+    failover_model: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct CircuitBreaker {
+    consecutive_failures: u32,
+    open_until: Option<std::time::Instant>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RetryMetrics {
+    retries: u64,
+    failovers: u64,
+}
+
+fn backoff_delay(config: &RetryConfig, attempt: u32, retry_after: Option<std::time::Duration>) -> std::time::Duration {
+    // The server's Retry-After wins; otherwise exponential backoff with full jitter
+    if let Some(retry_after) = retry_after {
+        return retry_after;
+    }
+    let ceiling = config.base_delay_ms.saturating_mul(1 << attempt.min(16)).min(config.max_delay_ms);
+    std::time::Duration::from_millis(rand::random::<u64>() % (ceiling + 1))
+}
+
+fn call_with_retry<T>(
+    config: &RetryConfig,
+    breakers: &mut HashMap<String, CircuitBreaker>,
+    metrics: &mut RetryMetrics,
+    model: &str,
+    call: impl Fn(&str) -> Result<T, (Box<dyn Error>, Option<std::time::Duration>)>,
+    // False for errors that fail the same way every time, e.g. an invalid request or bad credentials
+    retryable: impl Fn(&dyn Error) -> bool,
+) -> Result<T, Box<dyn Error>> {
+    let breaker = breakers.entry(model.to_string()).or_default();
+    let circuit_open = breaker.open_until.map_or(false, |until| std::time::Instant::now() < until);
+    // Why the primary model gave up, reported alongside any failover error
+    let mut primary_error = format!("{} circuit is open", model);
+
+    if !circuit_open {
+        for attempt in 0..=config.max_retries {
+            match call(model) {
+                Ok(value) => {
+                    breakers.insert(model.to_string(), CircuitBreaker::default());
+                    return Ok(value);
+                }
+                // Neither a retry nor the failover model would change the outcome, and the
+                // provider is healthy, so the circuit is left alone
+                Err((error, _)) if !retryable(error.as_ref()) => return Err(format!("{} failed: {}", model, error).into()),
+                Err((_, retry_after)) if attempt < config.max_retries => {
+                    metrics.retries += 1;
+                    std::thread::sleep(backoff_delay(config, attempt, retry_after));
+                }
+                Err((error, _)) => {
+                    primary_error = format!("{} failed: {}", model, error);
+                    break;
+                }
+            }
+        }
+        // Open the circuit after repeated failures so later calls fail over straight away
+        let breaker = breakers.entry(model.to_string()).or_default();
+        breaker.consecutive_failures += 1;
+        if breaker.consecutive_failures >= 3 {
+            breaker.open_until = Some(std::time::Instant::now() + std::time::Duration::from_secs(60));
+        }
+    }
+
+    match &config.failover_model {
+        Some(secondary) if secondary != model => {
+            metrics.failovers += 1;
+            call(secondary).map_err(|(error, _)| format!("{}; failover to {} failed: {}", primary_error, secondary, error).into())
+        }
+        _ => Err(format!("{}, and no failover is configured", primary_error).into()),
+    }
+}
+
+// Vault: Device Sync over QuDAG
+// Vaults exchange encrypted deltas; peers relaying them never see plaintext
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct VaultEntryVersion {
+    // Encrypted with the vault key, which only paired devices hold
+    ciphertext: Vec<u8>,
+    modified_at: u64,
+    device_id: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SyncedVault {
+    // Full history per entry; the newest version is the current value
+    entries: HashMap<String, Vec<VaultEntryVersion>>,
+    // Namespaces this device syncs; entries are named "<namespace>/<key>"
+    synced_namespaces: HashSet<String>,
+    // Ids of deltas already merged; gossip redelivers deltas and each must apply once
+    #[serde(default)]
+    applied_deltas: HashSet<String>,
+}
+
+// Returns false for a delta that was already merged
+fn merge_vault_delta(vault: &mut SyncedVault, delta_id: &str, delta: HashMap<String, VaultEntryVersion>) -> bool {
+    if !vault.applied_deltas.insert(delta_id.to_string()) {
+        return false;
+    }
+    for (key, incoming) in delta {
+        let namespace = key.split('/').next().unwrap_or("");
+        if !vault.synced_namespaces.contains(namespace) {
+            continue;
+        }
+        let history = vault.entries.entry(key).or_default();
+        // Last writer wins; ties break on device id so every device converges to the same value
+        let position = history
+            .iter()
+            .position(|v| (v.modified_at, &v.device_id) > (incoming.modified_at, &incoming.device_id))
+            .unwrap_or(history.len());
+        history.insert(position, incoming);
+    }
+    true
+}
+
+fn pairing_code() -> String {
+    // Shown on the existing device and typed into the new one; used to authenticate a PAKE exchange
+    // of the vault key, so the key itself never crosses the network unprotected
+    format!("{:06}", rand::random::<u32>() % 1_000_000)
+}
+
+// Exchange Market: Offer Management
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct OfferBook {
+    offers: HashMap<String, ResourceOffer>,
+}
+
+fn expire_offers(book: &mut OfferBook, now: u64) -> Vec<String> {
+    let expired: Vec<String> = book.offers.values().filter(|o| o.expires_at.map_or(false, |at| now >= at)).map(|o| o.id.clone()).collect();
+    for id in &expired {
+        book.offers.remove(id);
+    }
+    expired
+}
+
+fn amend_offer(book: &mut OfferBook, provider: &str, id: &str, price_per_hour: Option<u128>, cpu_cores: Option<u32>) -> Result<(), Box<dyn Error>> {
+    // Amendments keep the offer id, so existing references and search results stay valid
+    let offer = book.offers.get_mut(id).ok_or("no such offer")?;
+    if offer.provider != provider {
+        return Err("only the offering provider may amend it".into());
+    }
+    if let Some(price) = price_per_hour {
+        offer.price_per_hour = price;
+    }
+    if let Some(cores) = cpu_cores {
+        offer.cpu_cores = cores;
+    }
+    Ok(())
+}
+
+fn bulk_upsert_offers(book: &mut OfferBook, provider: &str, offers: Vec<ResourceOffer>) -> Result<usize, Box<dyn Error>> {
+    // All-or-nothing: check every offer before touching the book
+    // An existing id must belong to the caller too, or an upsert could take over another
+    // provider's offer
+    let owned = |o: &ResourceOffer| o.provider == provider && book.offers.get(&o.id).map_or(true, |existing| existing.provider == provider);
+    if !offers.iter().all(owned) {
+        return Err("bulk requests may only touch the caller's own offers".into());
+    }
+    let count = offers.len();
+    for offer in offers {
+        book.offers.insert(offer.id.clone(), offer);
+    }
+    Ok(count)
+}
+
+fn bulk_cancel_offers(book: &mut OfferBook, provider: &str, ids: &[String]) -> usize {
+    let before = book.offers.len();
+    book.offers.retain(|id, offer| !(offer.provider == provider && ids.contains(id)));
+    before - book.offers.len()
+}
+
+fn reconcile_offers(book: &OfferBook, provider: &str, desired: &[ResourceOffer]) -> (Vec<ResourceOffer>, Vec<String>) {
+    // Returns what to publish (new or changed) and what to cancel, so providers can declare their
+    // full offer set and let the exchange work out the difference
+    let published: HashMap<&String, &ResourceOffer> = book.offers.iter().filter(|(_, o)| o.provider == provider).collect();
+    let to_publish = desired
+        .iter()
+        .filter(|o| published.get(&o.id).map_or(true, |p| *p != o))
+        .cloned()
+        .collect();
+    let to_cancel = published.keys().filter(|id| !desired.iter().any(|o| &&o.id == *id)).map(|id| (*id).clone()).collect();
+    (to_publish, to_cancel)
+}
+
+// Rules Audit: Tamper-evident Log
+// Entries are hash-chained so truncation or modification is detectable
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AuditEntry {
+    sequence: u64,
+    timestamp: u64,
+    event: String,
+    previous_hash: String,
+    hash: String,
+}
+
+fn append_audit_entry(log: &mut Vec<AuditEntry>, event: &str, timestamp: u64) -> &AuditEntry {
+    let sequence = log.len() as u64;
+    let previous_hash = log.last().map_or_else(|| "0".repeat(64), |e| e.hash.clone());
+    let hash = audit_entry_hash(sequence, timestamp, event, &previous_hash);
+    log.push(AuditEntry { sequence, timestamp, event: event.to_string(), previous_hash, hash });
+    log.last().expect("entry was just pushed")
+}
+
+fn audit_entry_hash(sequence: u64, timestamp: u64, event: &str, previous_hash: &str) -> String {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&sequence.to_le_bytes());
+    hasher.update(&timestamp.to_le_bytes());
+    hasher.update(event.as_bytes());
+    hasher.update(previous_hash.as_bytes());
+    hasher.finalize().to_hex().to_string()
+}
+
+fn verify_audit_log(log: &[AuditEntry], anchored_head: Option<(u64, &str)>) -> Result<(), Box<dyn Error>> {
+    let mut previous = "0".repeat(64);
+    for (index, entry) in log.iter().enumerate() {
+        if entry.sequence != index as u64 || entry.previous_hash != previous {
+            return Err(format!("audit chain broken at entry {}", index).into());
+        }
+        if audit_entry_hash(entry.sequence, entry.timestamp, &entry.event, &entry.previous_hash) != entry.hash {
+            return Err(format!("audit entry {} was modified", index).into());
+        }
+        previous = entry.hash.clone();
+    }
+
+    // A consistent chain can still be truncated; the head anchored in the DAG catches that
+    if let Some((sequence, hash)) = anchored_head {
+        match log.get(sequence as usize) {
+            Some(entry) if entry.hash == hash => {}
+            _ => return Err(format!("audit log does not contain anchored entry {}", sequence).into()),
+        }
+    }
+    Ok(())
+}
+
+fn anchor_audit_head() -> Result<(), Box<dyn Error>> {
+    // Functionality to periodically write the head (sequence, hash) into the QuDAG DAG through
+    // the chain adapter, e.g. as an ExchangeCall payload
+    Ok(())
+}
+
+fn export_audit_log(log: &[AuditEntry], signing_key: &ed25519_dalek::SigningKey) -> Result<String, Box<dyn Error>> {
+    // Signed export for external compliance reviews
+    use ed25519_dalek::Signer;
+    let body = serde_json::to_string(log)?;
+    let signature = signing_key.sign(body.as_bytes());
+    Ok(serde_json::to_string(&serde_json::json!({ "entries": log, "signature": hex::encode(signature.to_bytes()) }))?)
+}
+
+// Exchange Core: Orderbook and Matching Engine
+// Price-time priority matching; every fill emits a trade event the ledger settles atomically
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum Side {
+    Buy,
+    Sell,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Order {
+    id: u64,
+    account: String,
+    side: Side,
+    // None for market orders
+    limit_price: Option<u128>,
+    quantity: u128,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TradeEvent {
+    maker_order: u64,
+    taker_order: u64,
+    buyer: String,
+    seller: String,
+    price: u128,
+    quantity: u128,
+}
+
+#[derive(Debug, Default)]
+struct OrderBook {
+    // Price level -> resting orders in arrival order
+    bids: BTreeMap<u128, VecDeque<Order>>,
+    asks: BTreeMap<u128, VecDeque<Order>>,
+}
+
+fn submit_order(book: &mut OrderBook, mut order: Order) -> Vec<TradeEvent> {
+    let mut trades = Vec::new();
+
+    while order.quantity > 0 {
+        // Best opposing level: lowest ask for a buy, highest bid for a sell
+        let best = match order.side {
+            Side::Buy => book.asks.iter_mut().next(),
+            Side::Sell => book.bids.iter_mut().next_back(),
+        };
+        let Some((&price, level)) = best else { break };
+        let crosses = match (order.side, order.limit_price) {
+            (_, None) => true,
+            (Side::Buy, Some(limit)) => price <= limit,
+            (Side::Sell, Some(limit)) => price >= limit,
+        };
+        if !crosses {
+            break;
+        }
+
+        // Oldest resting order at the level fills first
+        let maker = level.front_mut().expect("empty levels are removed");
+        let quantity = maker.quantity.min(order.quantity);
+        let (buyer, seller) = match order.side {
+            Side::Buy => (order.account.clone(), maker.account.clone()),
+            Side::Sell => (maker.account.clone(), order.account.clone()),
+        };
+        trades.push(TradeEvent { maker_order: maker.id, taker_order: order.id, buyer, seller, price, quantity });
+
+        maker.quantity -= quantity;
+        order.quantity -= quantity;
+        if maker.quantity == 0 {
+            level.pop_front();
+        }
+        if level.is_empty() {
+            match order.side {
+                Side::Buy => book.asks.remove(&price),
+                Side::Sell => book.bids.remove(&price),
+            };
+        }
+    }
+
+    // Unfilled limit orders rest on the book; unfilled market orders are dropped
+    if let (Some(price), true) = (order.limit_price, order.quantity > 0) {
+        let side = match order.side {
+            Side::Buy => &mut book.bids,
+            Side::Sell => &mut book.asks,
+        };
+        side.entry(price).or_default().push_back(order);
+    }
+
+    trades
+}
+
+fn cancel_order(book: &mut OrderBook, id: u64, account: &str) -> Result<Order, Box<dyn Error>> {
+    for side in [&mut book.bids, &mut book.asks] {
+        let found = side
+            .iter()
+            .find_map(|(price, level)| level.iter().position(|o| o.id == id && o.account == account).map(|position| (*price, position)));
+        if let Some((price, position)) = found {
+            let level = side.get_mut(&price).expect("level was just found");
+            let order = level.remove(position).expect("position is in range");
+            if level.is_empty() {
+                side.remove(&price);
+            }
+            return Ok(order);
+        }
+    }
+    Err(format!("no open order {} for {}", id, account).into())
+}
+
+fn settle_trade_event(ledger: &mut Ledger, trade: &TradeEvent, base: &AssetId) -> Result<(), Box<dyn Error>> {
+    // Both legs move together: if the rUv leg fails the asset leg is reversed
+    transfer_asset(ledger, &trade.seller, &trade.buyer, base, trade.quantity)?;
+    let cost = trade.price.checked_mul(trade.quantity).ok_or("trade value overflow")?;
+    if let Err(e) = transfer_asset(ledger, &trade.buyer, &trade.seller, &AssetId(RUV.to_string()), cost) {
+        transfer_asset(ledger, &trade.buyer, &trade.seller, base, trade.quantity)?;
+        return Err(e);
+    }
+    Ok(())
+}
+
+// Network Kademlia: Signed Records and Storage Quotas
+// Keeps spam out of the DHT that the market and discovery layers rely on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SignedRecord {
+    key: Vec<u8>,
+    value: Vec<u8>,
+    publisher: String,
+    expires_at: u64,
+    // Publisher's signature over key, value and expiry
+    signature: Vec<u8>,
+}
+
+#[derive(Debug, Default)]
+struct RecordStore {
+    records: HashMap<Vec<u8>, SignedRecord>,
+    bytes_per_publisher: HashMap<String, usize>,
+    quota_per_publisher: usize,
+    total_quota: usize,
+}
+
+fn signed_bytes(record: &SignedRecord) -> Vec<u8> {
+    let mut bytes = record.key.clone();
+    bytes.extend_from_slice(&record.value);
+    bytes.extend_from_slice(&record.expires_at.to_le_bytes());
+    bytes
+}
+
+fn put_record(store: &mut RecordStore, record: SignedRecord, verify: impl Fn(&str, &[u8], &[u8]) -> bool, reputation: &HashMap<String, f64>, now: u64) -> Result<(), Box<dyn Error>> {
+    if !verify(&record.publisher, &signed_bytes(&record), &record.signature) {
+        return Err("record signature does not match its publisher".into());
+    }
+    if record.expires_at <= now {
+        return Err("record already expired".into());
+    }
+
+    // Only the original publisher may overwrite a record
+    let replaced = match store.records.get(&record.key) {
+        Some(existing) if existing.publisher != record.publisher => return Err("record key is owned by another publisher".into()),
+        Some(existing) => existing.key.len() + existing.value.len(),
+        None => 0,
+    };
+
+    let size = record.key.len() + record.value.len();
+    let used = store.bytes_per_publisher.get(&record.publisher).copied().unwrap_or(0) - replaced;
+    if used + size > store.quota_per_publisher {
+        return Err(format!("{} is over its storage quota", record.publisher).into());
+    }
+    // When the store is full, records from lower-reputation publishers make room, lowest first.
+    // The victims are picked before anything is removed, so a put that can't fit changes nothing
+    let stored = store.bytes_per_publisher.values().sum::<usize>() - replaced;
+    let mut overflow = (stored + size).saturating_sub(store.total_quota);
+    let rank = |publisher: &str| reputation.get(publisher).copied().unwrap_or(0.0);
+    let own = rank(&record.publisher);
+    let mut candidates: Vec<&SignedRecord> = store.records.values().filter(|r| rank(&r.publisher) < own).collect();
+    candidates.sort_by(|a, b| rank(&a.publisher).total_cmp(&rank(&b.publisher)));
+    let mut victims = Vec::new();
+    for candidate in candidates {
+        if overflow == 0 {
+            break;
+        }
+        overflow = overflow.saturating_sub(candidate.key.len() + candidate.value.len());
+        victims.push(candidate.key.clone());
+    }
+    if overflow > 0 {
+        return Err("store is full of records from publishers with equal or higher reputation".into());
+    }
+
+    for key in victims {
+        if let Some(evicted) = store.records.remove(&key) {
+            *store.bytes_per_publisher.entry(evicted.publisher).or_default() -= evicted.key.len() + evicted.value.len();
+        }
+    }
+    if replaced > 0 {
+        store.records.remove(&record.key);
+        *store.bytes_per_publisher.entry(record.publisher.clone()).or_default() -= replaced;
+    }
+
+    *store.bytes_per_publisher.entry(record.publisher.clone()).or_default() += size;
+    store.records.insert(record.key.clone(), record);
+    Ok(())
+}
+
+fn get_record<'a>(store: &'a RecordStore, key: &[u8], now: u64) -> Option<&'a SignedRecord> {
+    // Expiry is checked on read and again before replicating a record to other peers
+    store.records.get(key).filter(|r| r.expires_at > now)
+}
+
+fn schedule_republish() -> Result<(), Box<dyn Error>> {
+    // Functionality to re-sign and republish our own records before they expire (e.g. at 80% of the TTL)
+    Ok(())
+}
+
+// Exchange Consensus Adapter: Parallel Signature Verification
+// Incoming transactions are verified in batches across the rayon pool before reaching consensus
+#[derive(Debug, Default)]
+struct VerificationCache {
+    // Transaction hash -> verification result, so gossip duplicates are only verified once
+    results: Mutex<HashMap<[u8; 32], bool>>,
+}
+
+fn verify_batch(
+    cache: &VerificationCache,
+    batch: Vec<ExchangeTransaction>,
+    verify: impl Fn(&ExchangeTransaction) -> bool + Sync,
+) -> Result<Vec<ExchangeTransaction>, Box<dyn Error>> {
+    let hashes = batch.iter().map(transaction_hash).collect::<Result<Vec<_>, _>>()?;
+
+    let verified: Vec<bool> = batch
+        .par_iter()
+        .zip(hashes.par_iter())
+        .map(|(tx, hash)| {
+            if let Some(result) = cache.results.lock().unwrap().get(hash) {
+                return *result;
+            }
+            let result = verify(tx);
+            cache.results.lock().unwrap().insert(*hash, result);
+            result
+        })
+        .collect();
+
+    // Only valid transactions are fed to consensus; invalid ones are dropped here
+    Ok(batch.into_iter().zip(verified).filter(|(_, ok)| *ok).map(|(tx, _)| tx).collect())
+}
+
+fn verify_incoming(cache: &VerificationCache, batch: Vec<ExchangeTransaction>) -> Result<Vec<ExchangeTransaction>, Box<dyn Error>> {
+    verify_batch(cache, batch, |tx| verify_signature(tx).is_ok())
+}
+
+fn benchmark_signature_pipeline() -> Result<(), Box<dyn Error>> {
+    // Functionality to measure transactions/sec for the old single-threaded path vs verify_batch,
+    // at batch sizes of 64, 256 and 1024, with and without cache hits
+    Ok(())
+}
+
+// Exchange Core: Atomic Batch Transfers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TransferRequest {
+    from: String,
+    to: String,
+    asset: AssetId,
+    amount: u128,
+    nonce: u64,
+}
+
+fn check_replay(ledger: &Ledger, request: &TransferRequest) -> Result<(), Box<dyn Error>> {
+    // Nonce/replay protection; called before any balance is touched
+    check_nonce(ledger, &request.from, request.nonce)
+}
+
+fn transfer(ledger: &mut Ledger, request: &TransferRequest, now: u64) -> Result<(), Box<dyn Error>> {
+    check_replay(ledger, request)?;
+    // Same path as any other transaction, so spending limits and nonces apply to batches too
+    let tx = ExchangeTransaction {
+        id: format!("{}:{}", request.from, request.nonce),
+        from: request.from.clone(),
+        to: request.to.clone(),
+        asset: request.asset.clone(),
+        amount: request.amount,
+        valid_until: None,
+        nonce: request.nonce,
+        fee: 0,
+        kind: TransactionType::Transfer,
+        memo: None,
+        public_key: Vec::new(),
+        signature: Vec::new(),
+    };
+    apply_transaction(ledger, &tx, now)
+}
+
+fn transfer_batch(ledger: &mut Ledger, requests: &[TransferRequest], now: u64) -> Result<(), Box<dyn Error>> {
+    // Either every transfer applies or none does: the batch runs against a copy that only
+    // replaces the ledger once every transfer has applied
+    let mut staged = ledger.clone();
+    for (applied, request) in requests.iter().enumerate() {
+        transfer(&mut staged, request, now).map_err(|e| format!("batch rolled back at transfer {}: {}", applied, e))?;
+    }
+    *ledger = staged;
+    Ok(())
+}
+
+// Exchange: ML-DSA Transaction Signing
+use qudag_crypto::{MlDsaKeyPair, MlDsaPublicKey};
+
+fn canonical_bytes(tx: &ExchangeTransaction) -> Vec<u8> {
+    // Fixed field order with length prefixes; the id and signature are derived from these bytes,
+    // so they are not part of them
+    let mut bytes = Vec::new();
+    for field in [tx.from.as_bytes(), tx.to.as_bytes(), tx.asset.0.as_bytes(), &tx.public_key] {
+        bytes.extend_from_slice(&(field.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(field);
+    }
+    bytes.extend_from_slice(&tx.amount.to_le_bytes());
+    bytes.extend_from_slice(&tx.nonce.to_le_bytes());
+    bytes.extend_from_slice(&tx.fee.to_le_bytes());
+    bytes.extend_from_slice(&canonical_kind(&tx.kind));
+    match &tx.memo {
+        Some(memo) => {
+            bytes.push(1);
+            bytes.extend_from_slice(&(memo.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(memo);
+        }
+        None => bytes.push(0),
+    }
+    match tx.valid_until {
+        Some(valid_until) => {
+            bytes.push(1);
+            bytes.extend_from_slice(&valid_until.to_le_bytes());
+        }
+        None => bytes.push(0),
+    }
+    bytes
+}
+
+fn transaction_id(tx: &ExchangeTransaction) -> String {
+    // Deterministic: the same signed content always gets the same id on every node
+    blake3::hash(&canonical_bytes(tx)).to_hex().to_string()
+}
+
+fn sign_transaction(tx: &mut ExchangeTransaction, keypair: &MlDsaKeyPair) -> Result<(), Box<dyn Error>> {
+    tx.public_key = keypair.public_key().to_vec();
+    tx.signature = keypair.sign(&canonical_bytes(tx))?;
+    tx.id = transaction_id(tx);
+    Ok(())
+}
+
+fn verify_signature(tx: &ExchangeTransaction) -> Result<(), Box<dyn Error>> {
+    if tx.id != transaction_id(tx) {
+        return Err(ExchangeError::new(ErrorCode::InvalidSignature, format!("transaction id {} does not match its contents", tx.id)).into());
+    }
+    // The key must own the sending account; otherwise any keypair could spend from any account,
+    // including pseudo-accounts like escrow:* and dark-registry that have no key at all
+    if account_id(&tx.public_key) != tx.from {
+        return Err(ExchangeError::new(ErrorCode::InvalidSignature, format!("signing key on {} does not belong to {}", tx.id, tx.from)).into());
+    }
+    let public_key = MlDsaPublicKey::from_bytes(&tx.public_key)?;
+    public_key
+        .verify(&canonical_bytes(tx), &tx.signature)
+        .map_err(|_| ExchangeError::new(ErrorCode::InvalidSignature, format!("bad signature on {}", tx.id)).into())
+}
+
+// CLI: Config Schema Validation and Migration
+// Backs `daa config validate` and `daa config migrate`
+const CONFIG_VERSION: u64 = 2;
+
+fn config_schema() -> serde_json::Value {
+    // One section per feature; unknown keys are errors so typos don't silently fall back to defaults
+    let section = |properties: serde_json::Value| serde_json::json!({ "type": "object", "additionalProperties": false, "properties": properties });
+    serde_json::json!({
+        "type": "object",
+        "additionalProperties": false,
+        "required": ["version"],
+        "properties": {
+            "version": { "type": "integer", "const": CONFIG_VERSION },
+            "chain": section(serde_json::json!({ "endpoint": { "type": "string" }, "network": { "enum": ["mainnet", "testnet", "local"] } })),
+            "economy": section(serde_json::json!({ "fee_model": { "type": "object" }, "treasury": { "type": "string" } })),
+            "rules": section(serde_json::json!({ "paths": { "type": "array", "items": { "type": "string" } } })),
+            "ai": section(serde_json::json!({ "model": { "type": "string" }, "max_tokens": { "type": "integer", "minimum": 1 } })),
+            "orchestrator": section(serde_json::json!({ "workers": { "type": "integer", "minimum": 1 }, "dlq_alert_threshold": { "type": "integer" } })),
+        },
+    })
+}
+
+fn did_you_mean<'a>(unknown: &str, known: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    known.map(|candidate| (strsim::levenshtein(unknown, candidate), candidate)).filter(|(distance, _)| *distance <= 2).min().map(|(_, candidate)| candidate)
+}
+
+fn validate_config(path: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let config: serde_json::Value = toml::from_str(&std::fs::read_to_string(path)?)?;
+    let schema = config_schema();
+    let validator = jsonschema::validator_for(&schema)?;
+
+    // Each diagnostic points at the exact key, e.g. "/ai/max_tokens: 0 is less than the minimum of 1"
+    let mut diagnostics = Vec::new();
+    for error in validator.iter_errors(&config) {
+        let mut message = format!("{}: {}", error.instance_path, error);
+        if let jsonschema::error::ValidationErrorKind::AdditionalProperties { unexpected } = &error.kind {
+            let section_schema = schema.pointer(&format!("{}/properties", error.schema_path.to_string().trim_end_matches("/additionalProperties")));
+            if let (Some(key), Some(serde_json::Value::Object(known))) = (unexpected.first(), section_schema) {
+                if let Some(suggestion) = did_you_mean(key, known.keys().map(String::as_str)) {
+                    message.push_str(&format!(" (did you mean `{}`?)", suggestion));
+                }
+            }
+        }
+        diagnostics.push(message);
+    }
+    Ok(diagnostics)
+}
+
+fn migrate_config(path: &str) -> Result<(), Box<dyn Error>> {
+    let mut config: toml::Table = toml::from_str(&std::fs::read_to_string(path)?)?;
+    let version = config.get("version").and_then(|v| v.as_integer()).unwrap_or(1);
+
+    // v1 -> v2: the top-level `agent_model` key moved to `ai.model`
+    if version < 2 {
+        if let Some(model) = config.remove("agent_model") {
+            config.entry("ai").or_insert_with(|| toml::Value::Table(Default::default())).as_table_mut().ok_or("`ai` must be a table")?.insert("model".to_string(), model);
+        }
+    }
+    config.insert("version".to_string(), toml::Value::Integer(CONFIG_VERSION as i64));
+
+    // Keep the original next to the upgraded file in case the migration needs undoing
+    std::fs::copy(path, format!("{}.v{}.bak", path, version))?;
+    std::fs::write(path, toml::to_string_pretty(&config)?)?;
+    Ok(())
+}
+
+// Exchange Core: Persistent Ledger Storage
+// A node restart must not lose balances: applied transactions go to a write-ahead log
+// before the ledger changes, and startup replays whatever the last snapshot missed
+trait LedgerStorage {
+    // `applied_at` is the `now` the transaction was applied with, so replay reaches the same result
+    fn append_wal(&mut self, sequence: u64, applied_at: u64, tx: &ExchangeTransaction) -> Result<(), Box<dyn Error>>;
+    fn wal_since(&self, sequence: u64) -> Result<Vec<(u64, u64, ExchangeTransaction)>, Box<dyn Error>>;
+    fn save_snapshot(&mut self, sequence: u64, ledger: &Ledger) -> Result<(), Box<dyn Error>>;
+    fn load_snapshot(&self) -> Result<Option<(u64, Ledger)>, Box<dyn Error>>;
+}
+
+#[derive(Default)]
+struct InMemoryStorage {
+    wal: Vec<(u64, u64, ExchangeTransaction)>,
+    snapshot: Option<(u64, Vec<u8>)>,
+}
+
+impl LedgerStorage for InMemoryStorage {
+    fn append_wal(&mut self, sequence: u64, applied_at: u64, tx: &ExchangeTransaction) -> Result<(), Box<dyn Error>> {
+        self.wal.push((sequence, applied_at, tx.clone()));
+        Ok(())
+    }
+
+    fn wal_since(&self, sequence: u64) -> Result<Vec<(u64, u64, ExchangeTransaction)>, Box<dyn Error>> {
+        Ok(self.wal.iter().filter(|(s, _, _)| *s > sequence).cloned().collect())
+    }
+
+    fn save_snapshot(&mut self, sequence: u64, ledger: &Ledger) -> Result<(), Box<dyn Error>> {
+        self.snapshot = Some((sequence, serde_json::to_vec(ledger)?));
+        // Entries covered by the snapshot are no longer needed
+        self.wal.retain(|(s, _, _)| *s > sequence);
+        Ok(())
+    }
+
+    fn load_snapshot(&self) -> Result<Option<(u64, Ledger)>, Box<dyn Error>> {
+        self.snapshot.as_ref().map(|(sequence, bytes)| Ok((*sequence, serde_json::from_slice(bytes)?))).transpose()
+    }
+}
+
+#[cfg(feature = "sled")]
+struct SledStorage {
+    wal: sled::Tree,
+    snapshots: sled::Tree,
+}
+
+#[cfg(feature = "sled")]
+impl LedgerStorage for SledStorage {
+    fn append_wal(&mut self, sequence: u64, applied_at: u64, tx: &ExchangeTransaction) -> Result<(), Box<dyn Error>> {
+        // Big-endian keys keep the tree ordered by sequence; flush fsyncs, so the entry is
+        // durable before the caller touches the ledger
+        self.wal.insert(sequence.to_be_bytes(), serde_json::to_vec(&(applied_at, tx))?)?;
+        self.wal.flush()?;
+        Ok(())
+    }
+
+    fn wal_since(&self, sequence: u64) -> Result<Vec<(u64, u64, ExchangeTransaction)>, Box<dyn Error>> {
+        self.wal
+            .range((sequence + 1).to_be_bytes()..)
+            .map(|entry| {
+                let (key, value) = entry?;
+                let (applied_at, tx) = serde_json::from_slice(&value)?;
+                Ok((u64::from_be_bytes(key.as_ref().try_into()?), applied_at, tx))
+            })
+            .collect()
+    }
+
+    fn save_snapshot(&mut self, sequence: u64, ledger: &Ledger) -> Result<(), Box<dyn Error>> {
+        self.snapshots.insert("latest", serde_json::to_vec(&(sequence, ledger))?)?;
+        self.snapshots.flush()?;
+        for key in self.wal.range(..=sequence.to_be_bytes()).keys() {
+            self.wal.remove(key?)?;
+        }
+        Ok(())
+    }
+
+    fn load_snapshot(&self) -> Result<Option<(u64, Ledger)>, Box<dyn Error>> {
+        self.snapshots.get("latest")?.map(|bytes| Ok(serde_json::from_slice(&bytes)?)).transpose()
+    }
+}
+
+fn recover_ledger(storage: &dyn LedgerStorage) -> Result<(u64, Ledger), Box<dyn Error>> {
+    let (mut sequence, mut ledger) = storage.load_snapshot()?.unwrap_or_default();
+    for (next, applied_at, tx) in storage.wal_since(sequence)? {
+        // Full apply_transaction at the original time, so nonces, fees, limits, escrows and
+        // domains come back exactly. The WAL is written before applying, so an entry may be
+        // one the ledger rejected; it is rejected again here
+        let _ = apply_transaction(&mut ledger, &tx, applied_at);
+        sequence = next;
+    }
+    Ok((sequence, ledger))
+}
+
+// Distributed Compute: Bandwidth-aware Reduction Topology
+// All-reduce rings are built from measured link quality instead of peer order
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+struct LinkStats {
+    latency_ms: f64,
+    bandwidth_mbps: f64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ReductionTopology {
+    // Peers grouped into latency clusters, each reduced as its own ring
+    rings: Vec<Vec<String>>,
+    // Exposed in swarm statistics to debug slow rounds
+    slowest_link: Option<(String, String, f64)>,
+}
+
+fn probe_links() -> Result<(), Box<dyn Error>> {
+    // Functionality to continuously measure latency (ping) and bandwidth (short transfers) between
+    // training peers, smoothed with an exponential moving average
+    Ok(())
+}
+
+fn build_topology(peers: &[String], links: &HashMap<(String, String), LinkStats>, cluster_latency_ms: f64) -> ReductionTopology {
+    let stats = |a: &String, b: &String| links.get(&(a.clone(), b.clone())).or_else(|| links.get(&(b.clone(), a.clone()))).copied().unwrap_or_default();
+
+    // Greedy clustering: a peer joins the first ring whose seed is within the latency bound
+    let mut rings: Vec<Vec<String>> = Vec::new();
+    for peer in peers {
+        match rings.iter_mut().find(|ring| stats(&ring[0], peer).latency_ms <= cluster_latency_ms) {
+            Some(ring) => ring.push(peer.clone()),
+            None => rings.push(vec![peer.clone()]),
+        }
+    }
+
+    // Within each ring, order peers nearest-neighbour so consecutive hops are fast links
+    for ring in rings.iter_mut() {
+        let mut ordered = vec![ring.remove(0)];
+        while !ring.is_empty() {
+            let last = ordered.last().expect("ring starts with its seed").clone();
+            let next = (0..ring.len()).max_by(|&i, &j| stats(&last, &ring[i]).bandwidth_mbps.total_cmp(&stats(&last, &ring[j]).bandwidth_mbps)).expect("ring is not empty");
+            ordered.push(ring.remove(next));
+        }
+        *ring = ordered;
+    }
+
+    let slowest_link = rings
+        .iter()
+        .flat_map(|ring| ring.iter().zip(ring.iter().cycle().skip(1)).take(if ring.len() > 1 { ring.len() } else { 0 }))
+        .map(|(a, b)| (a.clone(), b.clone(), stats(a, b).bandwidth_mbps))
+        .min_by(|x, y| x.2.total_cmp(&y.2));
+
+    ReductionTopology { rings, slowest_link }
+}
+
+fn reoptimize_topology() -> Result<(), Box<dyn Error>> {
+    // Functionality to rebuild the topology between rounds when link stats drift past a threshold,
+    // never mid-round, so all peers switch together
+    Ok(())
+}
+
+// Exchange WASM: QuDAGExchange on the Real Ledger
+// Replaces the localStorage mocks (fake keys, fixed 1000 balance) with the core ledger compiled to wasm
+#[wasm_bindgen]
+pub struct QuDAGExchange {
+    ledger: Ledger,
+    // Account id -> key seed; seeds only reach IndexedDB sealed under the vault secret
+    seeds: HashMap<String, [u8; 32]>,
+    // Bumped on every change, so a slow IndexedDB write never overwrites a newer state
+    revision: u64,
+    vault_secret: Vec<u8>,
+}
+
+// The whole state goes to IndexedDB in one write, so the ledger and the seeds that sign for it
+// can't drift apart; no separate WAL is needed
+#[derive(Serialize, Deserialize)]
+struct PersistedExchange {
+    revision: u64,
+    ledger: Ledger,
+    // JSON map of account -> seed, sealed like the protocol state store
+    seeds: EncryptedStateFile,
+}
+
+#[wasm_bindgen]
+impl QuDAGExchange {
+    // Restores the last persisted state, or starts empty on first load
+    pub async fn load(vault_secret: Vec<u8>) -> Result<QuDAGExchange, JsValue> {
+        let mut exchange = QuDAGExchange { ledger: Ledger::default(), seeds: HashMap::new(), revision: 0, vault_secret };
+        if let Some(saved) = read_persisted_exchange().await? {
+            let seeds = decrypt_state(&saved.seeds, &seed_vault_secret(&exchange.vault_secret)).map_err(|e| JsValue::from_str(&e.to_string()))?;
+            exchange.seeds = serde_json::from_slice(&seeds).map_err(|e| JsValue::from_str(&e.to_string()))?;
+            exchange.ledger = saved.ledger;
+            exchange.revision = saved.revision;
+        }
+        Ok(exchange)
+    }
+
+    // Resolves with the account id once its seed is stored, so the account can still sign after a reload
+    pub fn create_account(&mut self) -> Result<js_sys::Promise, JsValue> {
+        // A real embedded ML-DSA keypair; new accounts start at zero like on any other node
+        let mut seed = [0u8; 32];
+        getrandom::getrandom(&mut seed).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        let keypair = MlDsaKeyPair::from_seed(&seed).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        let account = account_id(&keypair.public_key());
+        self.seeds.insert(account.clone(), seed);
+        self.persist(JsValue::from_str(&account))
+    }
+
+    // Resolves with the transaction id once the new ledger state is stored
+    pub fn transfer(&mut self, from: &str, to: &str, amount: js_sys::BigInt) -> Result<js_sys::Promise, JsValue> {
+        let amount: u128 = parse_amount(amount)?.parse().map_err(|_| JsValue::from_str("invalid amount"))?;
+        let seed = self.seeds.get(from).ok_or_else(|| JsValue::from_str("unknown account"))?;
+        let keypair = MlDsaKeyPair::from_seed(seed).map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+        let mut tx = ExchangeTransaction {
+            id: String::new(),
+            from: from.to_string(),
+            to: to.to_string(),
+            asset: AssetId(RUV.to_string()),
+            amount,
+            valid_until: None,
+            nonce: next_nonce(&self.ledger, from),
+            fee: 0,
+            kind: TransactionType::Transfer,
+            memo: None,
+            public_key: Vec::new(),
+            signature: Vec::new(),
+        };
+        sign_transaction(&mut tx, &keypair).map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+        let now = (js_sys::Date::now() / 1000.0) as u64;
+        apply_transaction(&mut self.ledger, &tx, now).map_err(|e| match e.downcast_ref::<ExchangeError>() {
+            Some(error) => error_to_js(error),
+            None => JsValue::from_str(&e.to_string()),
+        })?;
+        self.persist(JsValue::from_str(&tx.id))
+    }
+
+    pub fn balance(&self, account: &str) -> js_sys::BigInt {
+        let amount = balance(&self.ledger, account, &AssetId(RUV.to_string()));
+        js_sys::BigInt::new(&JsValue::from_str(&amount.to_string())).expect("u128 fits in a BigInt")
+    }
+}
+
+impl QuDAGExchange {
+    // A change is durable once the returned promise resolves; a reload before then comes back
+    // with the previous state
+    fn persist(&mut self, result: JsValue) -> Result<js_sys::Promise, JsValue> {
+        self.revision += 1;
+        let seeds = serde_json::to_vec(&self.seeds).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        let seeds = seal_state(&seeds, &seed_vault_secret(&self.vault_secret), 1).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        let state = PersistedExchange { revision: self.revision, ledger: self.ledger.clone(), seeds };
+        let bytes = serde_json::to_string(&state).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        let revision = self.revision;
+        Ok(wasm_bindgen_futures::future_to_promise(async move {
+            write_persisted_exchange(revision, bytes).await?;
+            Ok(result)
+        }))
+    }
+}
+
+async fn open_exchange_db() -> Result<rexie::Rexie, JsValue> {
+    rexie::Rexie::builder("qudag-exchange").version(1).add_object_store(rexie::ObjectStore::new("state")).build().await.map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+async fn read_persisted_exchange() -> Result<Option<PersistedExchange>, JsValue> {
+    let db = open_exchange_db().await?;
+    let transaction = db.transaction(&["state"], rexie::TransactionMode::ReadOnly).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let store = transaction.store("state").map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let saved = store.get(JsValue::from_str("exchange")).await.map_err(|e| JsValue::from_str(&e.to_string()))?;
+    match saved.and_then(|value| value.as_string()) {
+        Some(json) => Ok(Some(serde_json::from_str(&json).map_err(|e| JsValue::from_str(&e.to_string()))?)),
+        None => Ok(None),
+    }
+}
+
+async fn write_persisted_exchange(revision: u64, state: String) -> Result<(), JsValue> {
+    // Read and write in one transaction, so of two overlapping writes the newer one wins
+    let db = open_exchange_db().await?;
+    let transaction = db.transaction(&["state"], rexie::TransactionMode::ReadWrite).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let store = transaction.store("state").map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let stored = store.get(JsValue::from_str("revision")).await.map_err(|e| JsValue::from_str(&e.to_string()))?;
+    if stored.and_then(|value| value.as_f64()).map_or(true, |stored| (stored as u64) < revision) {
+        store.put(&JsValue::from_f64(revision as f64), Some(&JsValue::from_str("revision"))).await.map_err(|e| JsValue::from_str(&e.to_string()))?;
+        store.put(&JsValue::from_str(&state), Some(&JsValue::from_str("exchange"))).await.map_err(|e| JsValue::from_str(&e.to_string()))?;
+    }
+    transaction.done().await.map_err(|e| JsValue::from_str(&e.to_string()))?;
+    Ok(())
+}
+
+// Exchange: Startup from Config and Graceful Shutdown
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum NetworkPreset {
+    Mainnet,
+    Testnet,
+    Local,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ExchangeConfig {
+    network: NetworkPreset,
+    // Overrides the preset's bootstrap peers when non-empty
+    bootstrap_peers: Vec<String>,
+    data_dir: String,
+    listen_addr: String,
+}
+
+struct Exchange {
+    config: ExchangeConfig,
+    ledger: std::sync::Arc<tokio::sync::RwLock<Ledger>>,
+    storage: Box<dyn LedgerStorage + Send + Sync>,
+    sequence: u64,
+    events: tokio::sync::broadcast::Sender<LedgerEvent>,
+    mempool: std::sync::Arc<tokio::sync::RwLock<Mempool>>,
+    fee_params: FeeModelParams,
+    // Agent accounts verified through the agent registry; they get the "verified" fee tier
+    verified_agents: HashSet<String>,
+    shutdown: tokio::sync::watch::Sender<bool>,
+    tasks: Vec<tokio::task::JoinHandle<()>>,
+}
+
+fn resolve_bootstrap_peers(config: &ExchangeConfig) -> Vec<String> {
+    if !config.bootstrap_peers.is_empty() {
+        return config.bootstrap_peers.clone();
+    }
+    match config.network {
+        NetworkPreset::Mainnet => vec!["/dns4/bootstrap1.qudag.network/tcp/8000".to_string(), "/dns4/bootstrap2.qudag.network/tcp/8000".to_string()],
+        NetworkPreset::Testnet => vec!["/dns4/testnet.qudag.network/tcp/8000".to_string()],
+        // Local networks discover each other over mDNS only
+        NetworkPreset::Local => Vec::new(),
+    }
+}
+
+fn open_storage(data_dir: &str) -> Result<Box<dyn LedgerStorage + Send + Sync>, Box<dyn Error>> {
+    #[cfg(feature = "sled")]
+    {
+        let db = sled::open(format!("{}/ledger", data_dir))?;
+        return Ok(Box::new(SledStorage { wal: db.open_tree("wal")?, snapshots: db.open_tree("snapshots")? }));
+    }
+    #[cfg(not(feature = "sled"))]
+    {
+        let _ = data_dir;
+        Ok(Box::new(InMemoryStorage::default()))
+    }
+}
+
+impl Exchange {
+    async fn with_config(config: ExchangeConfig) -> Result<Exchange, Box<dyn Error>> {
+        // Ledger first, so the node never serves reads before its state is recovered
+        let storage = open_storage(&config.data_dir)?;
+        let (sequence, ledger) = recover_ledger(storage.as_ref())?;
+        let ledger = std::sync::Arc::new(tokio::sync::RwLock::new(ledger));
+        let (shutdown, shutdown_rx) = tokio::sync::watch::channel(false);
+
+        let mut tasks = Vec::new();
+        for peer in resolve_bootstrap_peers(&config) {
+            let mut rx = shutdown_rx.clone();
+            tasks.push(tokio::spawn(async move {
+                // Dial the peer and keep the connection until shutdown
+                let _ = log_activity(&format!("connecting to bootstrap peer {}", peer));
+                let _ = rx.changed().await;
+            }));
+        }
+
+        // Then the consensus adapter, which feeds finalized rounds into the shared ledger
+        let mut rx = shutdown_rx.clone();
+        tasks.push(tokio::spawn(async move {
+            let _ = rx.changed().await;
+        }));
+
+        let (events, _) = tokio::sync::broadcast::channel(1024);
+        Ok(Exchange {
+            config,
+            ledger,
+            storage,
+            sequence,
+            events,
+            mempool: Default::default(),
+            fee_params: FeeModelParams::default(),
+            verified_agents: HashSet::new(),
+            shutdown,
+            tasks,
+        })
+    }
+
+    async fn shutdown(mut self) -> Result<(), Box<dyn Error>> {
+        // Stop accepting work, let every task finish its current step, then snapshot the ledger
+        // so the next start doesn't have to replay the whole WAL
+        self.shutdown.send(true)?;
+        for task in self.tasks.drain(..) {
+            task.await?;
+        }
+        let ledger = self.ledger.read().await;
+        self.storage.save_snapshot(self.sequence, &ledger)?;
+        Ok(())
+    }
+}
+
+// Exchange Market: Search
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+enum OfferSort {
+    #[default]
+    PriceAscending,
+    ReputationDescending,
+    MemoryDescending,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ResourceQuery {
+    resource_type: Option<String>,
+    class: Option<JobClass>,
+    min_memory_gb: Option<u32>,
+    max_price: Option<u128>,
+    region: Option<String>,
+    min_reputation: Option<f64>,
+    sort: OfferSort,
+    offset: usize,
+    limit: Option<usize>,
+}
+
+impl ResourceQuery {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn resource_type(mut self, resource_type: &str) -> Self {
+        self.resource_type = Some(resource_type.to_string());
+        self
+    }
+
+    fn class(mut self, class: JobClass) -> Self {
+        self.class = Some(class);
+        self
+    }
+
+    fn min_memory_gb(mut self, memory: u32) -> Self {
+        self.min_memory_gb = Some(memory);
+        self
+    }
+
+    fn max_price(mut self, price: u128) -> Self {
+        self.max_price = Some(price);
+        self
+    }
+
+    fn region(mut self, region: &str) -> Self {
+        self.region = Some(region.to_string());
+        self
+    }
+
+    fn min_reputation(mut self, reputation: f64) -> Self {
+        self.min_reputation = Some(reputation);
+        self
+    }
+
+    fn sort(mut self, sort: OfferSort) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    fn page(mut self, offset: usize, limit: usize) -> Self {
+        self.offset = offset;
+        self.limit = Some(limit);
+        self
+    }
+}
+
+fn search_offers(book: &OfferBook, reputation: &HashMap<String, f64>, query: &ResourceQuery, now: u64) -> Vec<ResourceOffer> {
+    // The book is the local view of offers gathered from DHT records and gossip; expired offers
+    // can still be in it until the next expire_offers sweep, so they are filtered here too
+    let reputation_of = |offer: &ResourceOffer| reputation.get(&offer.provider).copied().unwrap_or(0.0);
+    let mut matches: Vec<&ResourceOffer> = book
+        .offers
+        .values()
+        .filter(|o| o.expires_at.map_or(true, |at| now < at))
+        .filter(|o| query.resource_type.as_ref().map_or(true, |t| &o.resource_type == t))
+        .filter(|o| query.class.map_or(true, |class| o.class == class))
+        .filter(|o| query.min_memory_gb.map_or(true, |memory| o.memory_gb >= memory))
+        .filter(|o| query.max_price.map_or(true, |price| o.price_per_hour <= price))
+        .filter(|o| query.region.as_ref().map_or(true, |region| &o.region == region))
+        .filter(|o| query.min_reputation.map_or(true, |min| reputation_of(o) >= min))
+        .collect();
+
+    // Offer id breaks ties so pages are stable between calls
+    match query.sort {
+        OfferSort::PriceAscending => matches.sort_by(|a, b| (a.price_per_hour, &a.id).cmp(&(b.price_per_hour, &b.id))),
+        OfferSort::ReputationDescending => matches.sort_by(|a, b| reputation_of(b).total_cmp(&reputation_of(a)).then_with(|| a.id.cmp(&b.id))),
+        OfferSort::MemoryDescending => matches.sort_by(|a, b| b.memory_gb.cmp(&a.memory_gb).then_with(|| a.id.cmp(&b.id))),
+    }
+
+    matches.into_iter().skip(query.offset).take(query.limit.unwrap_or(usize::MAX)).cloned().collect()
+}
+
+// Exchange Market: Reservation Lifecycle
+// The consumer's rUv for the whole duration is held in a per-reservation account, paid out
+// to the provider for time actually used and refunded for the rest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Reservation {
+    id: String,
+    offer_id: String,
+    consumer: String,
+    provider: String,
+    price_per_hour: u128,
+    starts_at: u64,
+    ends_at: u64,
+    released: bool,
+}
+
+fn reservation_account(id: &str) -> String {
+    format!("reservation:{}", id)
+}
+
+fn reserve_resources(ledger: &mut Ledger, offer: &ResourceOffer, consumer: &str, hours: u64, now: u64) -> Result<Reservation, Box<dyn Error>> {
+    // Salted with the ledger's reservation counter: two reservations of one offer by one consumer
+    // in the same second get distinct escrow accounts, and every replica and WAL replay derives
+    // the same id
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(format!("{}:{}:{}", offer.id, consumer, now).as_bytes());
+    hasher.update(&ledger.reservations_opened.to_le_bytes());
+    let id = hasher.finalize().to_hex().to_string();
+    if ledger.balances.contains_key(&reservation_account(&id)) {
+        return Err("reservation id collision".into());
+    }
+    let reservation = Reservation {
+        id,
+        offer_id: offer.id.clone(),
+        consumer: consumer.to_string(),
+        provider: offer.provider.clone(),
+        price_per_hour: offer.price_per_hour,
+        starts_at: now,
+        ends_at: now + hours * 3600,
+        released: false,
+    };
+    let cost = offer.price_per_hour.checked_mul(hours as u128).ok_or("reservation cost overflow")?;
+    transfer_asset(ledger, consumer, &reservation_account(&reservation.id), &AssetId(RUV.to_string()), cost)?;
+    ledger.reservations_opened += 1;
+    Ok(reservation)
+}
+
+fn extend_reservation(ledger: &mut Ledger, reservation: &mut Reservation, hours: u64, now: u64) -> Result<(), Box<dyn Error>> {
+    if reservation.released || now >= reservation.ends_at {
+        return Err("only active reservations can be extended".into());
+    }
+    let cost = reservation.price_per_hour.checked_mul(hours as u128).ok_or("reservation cost overflow")?;
+    transfer_asset(ledger, &reservation.consumer, &reservation_account(&reservation.id), &AssetId(RUV.to_string()), cost)?;
+    reservation.ends_at += hours * 3600;
+    Ok(())
+}
+
+fn release_reservation(ledger: &mut Ledger, reservation: &mut Reservation, now: u64) -> Result<(), Box<dyn Error>> {
+    if reservation.released {
+        return Err("reservation already released".into());
+    }
+    let ruv = AssetId(RUV.to_string());
+    let account = reservation_account(&reservation.id);
+    let held = balance(ledger, &account, &ruv);
+
+    // Whole started hours are billed; everything else goes back to the consumer
+    let used_secs = now.min(reservation.ends_at).saturating_sub(reservation.starts_at);
+    let used = (reservation.price_per_hour * ((used_secs + 3599) / 3600) as u128).min(held);
+    transfer_asset(ledger, &account, &reservation.provider, &ruv, used)?;
+    transfer_asset(ledger, &account, &reservation.consumer, &ruv, held - used)?;
+    reservation.released = true;
+    Ok(())
+}
+
+fn expire_reservations(ledger: &mut Ledger, reservations: &mut [Reservation], now: u64) -> Result<(), Box<dyn Error>> {
+    // Run every block: reservations past their end are released automatically
+    for reservation in reservations.iter_mut().filter(|r| !r.released && now >= r.ends_at) {
+        release_reservation(ledger, reservation, now)?;
+    }
+    Ok(())
+}
+
+// Exchange Core: Escrow for Resource Trades
+// The consumer locks rUv; it is released to the provider on the consumer's approval or the
+// provider's signed completion report, and refunded to the consumer if neither arrives in time
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+enum TransactionType {
+    #[default]
+    Transfer,
+    EscrowLock { escrow_id: String, provider: String, timeout_at: u64 },
+    // Sent by the consumer, or by the provider with its signed completion report
+    EscrowRelease { escrow_id: String, #[serde(default)] completion: Option<CompletionReport> },
+    EscrowRefund { escrow_id: String },
+    // One transaction paying every contributor share of a payout run
+    FeeDistribution { payouts: Vec<(String, u128)> },
+    // .dark domain operations; the amount is the registration or renewal fee paid to `to`
+    DomainRegister { name: String, expires_at: u64 },
+    DomainRenew { name: String, expires_at: u64 },
+    // Ownership passes to `to`; the amount may carry a sale price
+    DomainTransfer { name: String },
+}
+
+fn canonical_kind(kind: &TransactionType) -> Vec<u8> {
+    let (tag, fields): (u8, Vec<&[u8]>) = match kind {
+        TransactionType::Transfer => (0, vec![]),
+        TransactionType::EscrowLock { escrow_id, provider, .. } => (1, vec![escrow_id.as_bytes(), provider.as_bytes()]),
+        TransactionType::EscrowRelease { escrow_id, .. } => (2, vec![escrow_id.as_bytes()]),
+        TransactionType::EscrowRefund { escrow_id } => (3, vec![escrow_id.as_bytes()]),
+        TransactionType::FeeDistribution { payouts } => (4, payouts.iter().map(|(account, _)| account.as_bytes()).collect()),
+        TransactionType::DomainRegister { name, .. } => (5, vec![name.as_bytes()]),
+        TransactionType::DomainRenew { name, .. } => (6, vec![name.as_bytes()]),
+        TransactionType::DomainTransfer { name } => (7, vec![name.as_bytes()]),
+    };
+    let mut bytes = vec![tag];
+    for field in fields {
+        bytes.extend_from_slice(&(field.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(field);
+    }
+    if let TransactionType::EscrowLock { timeout_at, .. } = kind {
+        bytes.extend_from_slice(&timeout_at.to_le_bytes());
+    }
+    if let TransactionType::EscrowRelease { completion, .. } = kind {
+        // The report is signed on its own; the transaction signature only needs to bind it
+        let report = completion.as_ref().and_then(|r| serde_json::to_vec(r).ok()).unwrap_or_default();
+        bytes.extend_from_slice(blake3::hash(&report).as_bytes());
+    }
+    if let TransactionType::FeeDistribution { payouts } = kind {
+        for (_, amount) in payouts {
+            bytes.extend_from_slice(&amount.to_le_bytes());
+        }
+    }
+    if let TransactionType::DomainRegister { expires_at, .. } | TransactionType::DomainRenew { expires_at, .. } = kind {
+        bytes.extend_from_slice(&expires_at.to_le_bytes());
+    }
+    bytes
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum EscrowState {
+    Locked,
+    Released,
+    Refunded,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Escrow {
+    id: String,
+    consumer: String,
+    provider: String,
+    amount: u128,
+    timeout_at: u64,
+    state: EscrowState,
+}
+
+fn escrow_account(id: &str) -> String {
+    format!("escrow:{}", id)
+}
+
+fn lock_escrow(ledger: &mut Ledger, tx: &ExchangeTransaction) -> Result<(), Box<dyn Error>> {
+    let TransactionType::EscrowLock { escrow_id, provider, timeout_at } = &tx.kind else {
+        return Err("not an escrow lock".into());
+    };
+    if ledger.escrows.contains_key(escrow_id) {
+        return Err(format!("escrow {} already exists", escrow_id).into());
+    }
+    transfer_asset(ledger, &tx.from, &escrow_account(escrow_id), &AssetId(RUV.to_string()), tx.amount)?;
+    ledger.escrows.insert(escrow_id.clone(), Escrow {
+        id: escrow_id.clone(),
+        consumer: tx.from.clone(),
+        provider: provider.clone(),
+        amount: tx.amount,
+        timeout_at: *timeout_at,
+        state: EscrowState::Locked,
+    });
+    Ok(())
+}
+
+fn locked_escrow(ledger: &Ledger, escrow_id: &str) -> Result<Escrow, Box<dyn Error>> {
+    let escrow = ledger.escrows.get(escrow_id).ok_or_else(|| format!("no escrow {}", escrow_id))?;
+    if escrow.state != EscrowState::Locked {
+        return Err(format!("escrow {} is not locked", escrow_id).into());
+    }
+    Ok(escrow.clone())
+}
+
+fn release_escrow(ledger: &mut Ledger, tx: &ExchangeTransaction) -> Result<(), Box<dyn Error>> {
+    let TransactionType::EscrowRelease { escrow_id, completion } = &tx.kind else {
+        return Err("not an escrow release".into());
+    };
+    let escrow = locked_escrow(ledger, escrow_id)?;
+    // tx.public_key is already proven to own tx.from, so it is the provider's key here
+    let proven = |report: &CompletionReport| report.escrow_id == escrow.id && MlDsaPublicKey::from_bytes(&tx.public_key).map_or(false, |key| verify_completion_report(report, &key));
+    let authorized = tx.from == escrow.consumer || (tx.from == escrow.provider && completion.as_ref().map_or(false, proven));
+    if !authorized {
+        return Err(ExchangeError::new(ErrorCode::Unauthorized, "release needs the consumer's approval or the provider's signed completion report").into());
+    }
+    transfer_asset(ledger, &escrow_account(&escrow.id), &escrow.provider, &AssetId(RUV.to_string()), escrow.amount)?;
+    ledger.escrows.get_mut(escrow_id).expect("checked above").state = EscrowState::Released;
+    Ok(())
+}
+
+fn refund_escrow(ledger: &mut Ledger, tx: &ExchangeTransaction, now: u64) -> Result<(), Box<dyn Error>> {
+    let TransactionType::EscrowRefund { escrow_id } = &tx.kind else {
+        return Err("not an escrow refund".into());
+    };
+    let escrow = locked_escrow(ledger, escrow_id)?;
+    // Disputed or abandoned escrows fall back to a refund once the timeout passes
+    if tx.from != escrow.consumer || now < escrow.timeout_at {
+        return Err(format!("escrow {} cannot be refunded yet", escrow.id).into());
+    }
+    transfer_asset(ledger, &escrow_account(&escrow.id), &escrow.consumer, &AssetId(RUV.to_string()), escrow.amount)?;
+    ledger.escrows.get_mut(escrow_id).expect("checked above").state = EscrowState::Refunded;
+    Ok(())
+}
+
+// Exchange: Streaming Status and Events
+// Wallets and UIs subscribe instead of polling wait_for_confirmation
+use futures::{Stream, StreamExt};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum LedgerEvent {
+    StatusChanged { tx_id: String, status: TransactionStatus },
+    BalanceChanged { account: String, asset: AssetId, balance: u128 },
+    // Transactions from a reorganized round go back to Pending before being re-applied
+    Reorg { round: u64, tx_ids: Vec<String> },
+}
+
+impl Exchange {
+    fn events(&self) -> impl Stream<Item = LedgerEvent> {
+        // Slow subscribers that fall behind the channel skip missed events rather than blocking the node
+        tokio_stream::wrappers::BroadcastStream::new(self.events.subscribe()).filter_map(|event| async move { event.ok() })
+    }
+
+    async fn subscribe_transaction(&self, tx_id: &str) -> impl Stream<Item = TransactionStatus> {
+        let tx_id = tx_id.to_string();
+        // Subscribe before reading the current status, so a change in between is not missed;
+        // a transaction that is already final yields its status and the stream ends
+        let live = self.events();
+        let current = self.mempool.read().await.statuses.get(&tx_id).cloned();
+        let updates = live
+            .filter_map(move |event| {
+                let tx_id = tx_id.clone();
+                async move {
+                    match event {
+                        LedgerEvent::StatusChanged { tx_id: id, status } if id == tx_id => Some(status),
+                        LedgerEvent::Reorg { tx_ids, .. } if tx_ids.contains(&tx_id) => Some(TransactionStatus::Pending),
+                        _ => None,
+                    }
+                }
+            });
+        futures::stream::iter(current)
+            .chain(updates)
+            // The stream ends once the transaction reaches a final state
+            .scan(false, |done, status| {
+                if *done {
+                    return futures::future::ready(None);
+                }
+                *done = matches!(status, TransactionStatus::Finalized | TransactionStatus::Rejected(_) | TransactionStatus::Expired);
+                futures::future::ready(Some(status))
+            })
+    }
+
+    fn publish(&self, event: LedgerEvent) {
+        // No subscribers is not an error
+        let _ = self.events.send(event);
+    }
+}
+
+// Exchange Accounts: Deterministic Key Derivation
+// One mnemonic backs up every account. ML-DSA has no BIP-32 style public derivation,
+// so each path hashes down to its own 32-byte keygen seed (hardened-only)
+const DERIVATION_PREFIX: &str = "m/qudag'/0'";
+
+fn account_id(public_key: &[u8]) -> String {
+    blake3::hash(public_key).to_hex().to_string()
+}
+
+fn derivation_path(account_index: u32) -> String {
+    format!("{}/{}'", DERIVATION_PREFIX, account_index)
+}
+
+fn create_mnemonic() -> Result<bip39::Mnemonic, Box<dyn Error>> {
+    let mut entropy = [0u8; 32];
+    getrandom::getrandom(&mut entropy)?;
+    Ok(bip39::Mnemonic::from_entropy(&entropy)?)
+}
+
+fn derive_account_key(mnemonic: &bip39::Mnemonic, passphrase: &str, account_index: u32) -> Result<MlDsaKeyPair, Box<dyn Error>> {
+    let seed = mnemonic.to_seed(passphrase);
+    let mut hasher = blake3::Hasher::new_derive_key("qudag exchange account key v1");
+    hasher.update(&seed);
+    hasher.update(derivation_path(account_index).as_bytes());
+    Ok(MlDsaKeyPair::from_seed(hasher.finalize().as_bytes())?)
+}
+
+fn seed_vault_secret(vault_secret: &[u8]) -> [u8; 32] {
+    // Its own context, so the seed is never encrypted under the same key as protocol state
+    blake3::derive_key("qudag exchange seed vault v1", vault_secret)
+}
+
+fn store_seed_in_vault(mnemonic: &bip39::Mnemonic, vault_path: &str, vault_secret: &[u8]) -> Result<(), Box<dyn Error>> {
+    // Same at-rest encryption format as the protocol state store
+    write_encrypted_state(vault_path, mnemonic.to_string().as_bytes(), &seed_vault_secret(vault_secret), 1)
+}
+
+fn load_seed_from_vault(vault_path: &str, vault_secret: &[u8]) -> Result<bip39::Mnemonic, Box<dyn Error>> {
+    let file: EncryptedStateFile = serde_json::from_slice(&std::fs::read(vault_path)?)?;
+    let plaintext = decrypt_state(&file, &seed_vault_secret(vault_secret))?;
+    Ok(bip39::Mnemonic::parse(String::from_utf8(plaintext)?)?)
+}
+
+fn recover_accounts(ledger: &Ledger, mnemonic: &bip39::Mnemonic, passphrase: &str, gap_limit: u32) -> Result<Vec<(u32, String)>, Box<dyn Error>> {
+    // Walk indices until `gap_limit` consecutive accounts have never been used on the ledger
+    let mut recovered = Vec::new();
+    let mut unused = 0;
+    let mut index = 0;
+    while unused < gap_limit {
+        let keypair = derive_account_key(mnemonic, passphrase, index)?;
+        let account = account_id(&keypair.public_key());
+        if ledger.balances.contains_key(&account) {
+            recovered.push((index, account));
+            unused = 0;
+        } else {
+            unused += 1;
+        }
+        index += 1;
+    }
+    Ok(recovered)
+}
+
+// Exchange: Fee Estimation
+// The same estimator backs TransactionResult.estimated_fee, `/v1/fees/estimate`, and the WASM binding;
+// the estimate is exactly what apply_transaction charges: the signed fee plus the memo fee
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TransactionResult {
+    tx_id: String,
+    status: TransactionStatus,
+    estimated_fee: Option<u128>,
+}
+
+impl Exchange {
+    async fn model_fee(&self, tx: &ExchangeTransaction) -> u128 {
+        // Congestion is the current mempool depth; verified agents get their tier discount
+        let mempool_size = self.mempool.read().await.pending.len();
+        let tier = if self.verified_agents.contains(&tx.from) { "verified" } else { "unverified" };
+        fee_for(&self.fee_params, tier, mempool_size)
+    }
+
+    async fn estimate_fee(&self, tx: &ExchangeTransaction) -> u128 {
+        // The memo part comes from the ledger's cost model, the one apply_transaction charges
+        let memo = memo_fee(&self.ledger.read().await.cost_model, tx);
+        self.model_fee(tx).await + memo
+    }
+}
+
+#[wasm_bindgen]
+pub fn estimate_fee(fee_params_json: &str, cost_model_json: &str, verified: bool, mempool_size: usize, memo_bytes: usize) -> Result<js_sys::BigInt, JsValue> {
+    // Browser clients fetch the current params, cost model and mempool size from a node, then estimate locally
+    let params: FeeModelParams = serde_json::from_str(fee_params_json).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let cost_model: CostModel = serde_json::from_str(cost_model_json).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let fee = fee_for(&params, if verified { "verified" } else { "unverified" }, mempool_size) + memo_bytes as u128 * cost_model.per_byte_fee;
+    js_sys::BigInt::new(&JsValue::from_str(&fee.to_string())).map_err(|_| JsValue::from_str("fee does not fit in a BigInt"))
+}
+
+// Exchange: Transaction Memos
+const MAX_MEMO_BYTES: usize = 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CostModel {
+    // rUv charged per memo byte, on top of the base transaction fee
+    per_byte_fee: u128,
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        CostModel { per_byte_fee: 1 }
+    }
+}
+
+fn memo_fee(cost_model: &CostModel, tx: &ExchangeTransaction) -> u128 {
+    tx.memo.as_ref().map_or(0, |memo| memo.len() as u128 * cost_model.per_byte_fee)
+}
+
+impl ExchangeTransaction {
+    fn memo(mut self, memo: impl Into<Vec<u8>>) -> Result<Self, Box<dyn Error>> {
+        // Builder-style, matching the `.memo(...)` in the transaction builder docs
+        let memo = memo.into();
+        if memo.len() > MAX_MEMO_BYTES {
+            return Err(ExchangeError::new(ErrorCode::InvalidTransaction, format!("memo exceeds {} bytes", MAX_MEMO_BYTES)).into());
+        }
+        self.memo = Some(memo);
+        Ok(self)
+    }
+}
+
+// Exchange: Nonces and Replay Protection
+// Each account's transactions apply in strict nonce order; the mempool holds
+// transactions that arrive ahead of a gap until the missing nonces show up
+const MAX_NONCE_GAP: u64 = 16;
+
+fn next_nonce(ledger: &Ledger, account: &str) -> u64 {
+    ledger.nonces.get(account).copied().unwrap_or(0)
+}
+
+fn check_nonce(ledger: &Ledger, account: &str, nonce: u64) -> Result<(), Box<dyn Error>> {
+    let expected = next_nonce(ledger, account);
+    if nonce < expected {
+        return Err(ExchangeError::new(ErrorCode::BadNonce, format!("nonce {} from {} was already used", nonce, account)).into());
+    }
+    if nonce > expected {
+        return Err(ExchangeError::new(ErrorCode::BadNonce, format!("nonce {} from {} is out of order, expected {}", nonce, account, expected)).into());
+    }
+    Ok(())
+}
+
+fn admit_to_mempool(mempool: &mut Mempool, ledger: &Ledger, tx: ExchangeTransaction) -> Result<(), Box<dyn Error>> {
+    let expected = next_nonce(ledger, &tx.from);
+    if tx.nonce < expected {
+        return Err(ExchangeError::new(ErrorCode::BadNonce, format!("nonce {} from {} was already used", tx.nonce, tx.from)).into());
+    }
+    // Future nonces are held, but only within a bounded gap so one account can't fill the pool
+    if tx.nonce >= expected + MAX_NONCE_GAP {
+        return Err(ExchangeError::new(ErrorCode::BadNonce, format!("nonce {} from {} is too far ahead of {}", tx.nonce, tx.from, expected)).into());
+    }
+    mempool.statuses.insert(tx.id.clone(), TransactionStatus::Pending);
+    mempool.pending.insert(tx.id.clone(), tx);
+    Ok(())
+}
+
+fn ready_transactions(mempool: &Mempool, ledger: &Ledger) -> Vec<ExchangeTransaction> {
+    // Only the contiguous run starting at each sender's next nonce is ready for consensus
+    let mut by_sender: HashMap<&str, BTreeMap<u64, &ExchangeTransaction>> = HashMap::new();
+    for tx in mempool.pending.values() {
+        by_sender.entry(&tx.from).or_default().insert(tx.nonce, tx);
+    }
+
+    let mut ready = Vec::new();
+    for (sender, txs) in by_sender {
+        let mut expected = next_nonce(ledger, sender);
+        while let Some(tx) = txs.get(&expected) {
+            ready.push((*tx).clone());
+            expected += 1;
+        }
+    }
+    ready
+}
+
+impl Exchange {
+    async fn get_next_nonce(&self, account: &str) -> u64 {
+        // Accounts for transactions still waiting in the mempool, so a wallet can queue several in a row
+        let ledger = self.ledger.read().await;
+        let mempool = self.mempool.read().await;
+        let pending = ready_transactions(&mempool, &ledger).iter().filter(|tx| tx.from == account).count() as u64;
+        next_nonce(&ledger, account) + pending
+    }
+}
+
+// Exchange: Light-client Balance Proofs
+// A Merkle commitment over (account, asset, balance) leaves, with the root published in DAG
+// vertices, lets browser clients check a balance without holding the full state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BalanceProof {
+    account: String,
+    asset: AssetId,
+    balance: u128,
+    leaf_index: usize,
+    // Committed in the root; fixes which levels promote the leaf's ancestor without a sibling
+    leaf_count: usize,
+    // Sibling hashes from the leaf up to the root, skipping levels where there is none
+    siblings: Vec<[u8; 32]>,
+}
+
+fn balance_leaf(account: &str, asset: &AssetId, balance: u128) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new();
+    // Domain-separate leaves from inner nodes so a node can't be passed off as a leaf
+    hasher.update(&[0]);
+    hasher.update(&(account.len() as u32).to_le_bytes());
+    hasher.update(account.as_bytes());
+    hasher.update(&(asset.0.len() as u32).to_le_bytes());
+    hasher.update(asset.0.as_bytes());
+    hasher.update(&balance.to_le_bytes());
+    *hasher.finalize().as_bytes()
+}
+
+fn merkle_parent(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[1]);
+    hasher.update(left);
+    hasher.update(right);
+    *hasher.finalize().as_bytes()
+}
+
+fn sorted_leaves(ledger: &Ledger) -> Vec<(String, AssetId, u128)> {
+    let mut leaves: Vec<(String, AssetId, u128)> = ledger
+        .balances
+        .iter()
+        .flat_map(|(account, balances)| balances.iter().map(move |(asset, balance)| (account.clone(), asset.clone(), *balance)))
+        .collect();
+    leaves.sort_by(|a, b| (&a.0, &a.1 .0).cmp(&(&b.0, &b.1 .0)));
+    leaves
+}
+
+fn merkle_levels(leaves: &[(String, AssetId, u128)]) -> Vec<Vec<[u8; 32]>> {
+    let mut levels = vec![leaves.iter().map(|(account, asset, balance)| balance_leaf(account, asset, *balance)).collect::<Vec<_>>()];
+    while levels.last().map_or(false, |level| level.len() > 1) {
+        let level = levels.last().expect("checked above");
+        // An odd node out moves up unchanged; pairing it with itself would give [a, b, c] and
+        // [a, b, c, c] the same root
+        let next = level.chunks(2).map(|pair| if let [left, right] = pair { merkle_parent(left, right) } else { pair[0] }).collect();
+        levels.push(next);
+    }
+    levels
+}
+
+fn committed_root(top: &[u8; 32], leaf_count: usize) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[2]);
+    hasher.update(&(leaf_count as u64).to_le_bytes());
+    hasher.update(top);
+    *hasher.finalize().as_bytes()
+}
+
+fn state_root(ledger: &Ledger) -> [u8; 32] {
+    let leaves = sorted_leaves(ledger);
+    let top = merkle_levels(&leaves).last().and_then(|level| level.first().copied()).unwrap_or([0; 32]);
+    committed_root(&top, leaves.len())
+}
+
+impl Exchange {
+    async fn get_balance_proof(&self, account: &str) -> Result<BalanceProof, Box<dyn Error>> {
+        let ledger = self.ledger.read().await;
+        let ruv = AssetId(RUV.to_string());
+        let leaves = sorted_leaves(&ledger);
+        let leaf_index = leaves.iter().position(|(a, asset, _)| a == account && *asset == ruv).ok_or("account has no rUv balance")?;
+        let levels = merkle_levels(&leaves);
+
+        let mut siblings = Vec::new();
+        let mut index = leaf_index;
+        for level in &levels[..levels.len() - 1] {
+            if let Some(sibling) = level.get(index ^ 1) {
+                siblings.push(*sibling);
+            }
+            index /= 2;
+        }
+        Ok(BalanceProof { account: account.to_string(), asset: ruv, balance: leaves[leaf_index].2, leaf_index, leaf_count: leaves.len(), siblings })
+    }
+}
+
+fn verify_balance_proof(proof: &BalanceProof, root: &[u8; 32]) -> bool {
+    // Runs in the browser against a root taken from a finalized DAG vertex
+    if proof.leaf_index >= proof.leaf_count {
+        return false;
+    }
+    let mut hash = balance_leaf(&proof.account, &proof.asset, proof.balance);
+    let mut index = proof.leaf_index;
+    let mut width = proof.leaf_count;
+    let mut siblings = proof.siblings.iter();
+    while width > 1 {
+        // Mirrors merkle_levels: the last node of an odd-width level has no sibling
+        if index != width - 1 || width % 2 == 0 {
+            let Some(sibling) = siblings.next() else { return false };
+            hash = if index % 2 == 0 { merkle_parent(&hash, sibling) } else { merkle_parent(sibling, &hash) };
+        }
+        index /= 2;
+        width = width.div_ceil(2);
+    }
+    siblings.next().is_none() && &committed_root(&hash, proof.leaf_count) == root
+}
+
+// Exchange Core: Zero-knowledge Balance Proofs
+// Pedersen commitments hide balances; a Bulletproofs range proof on (balance - threshold)
+// shows the balance is at least the threshold without revealing it
+mod zkp {
+    use bulletproofs::{BulletproofGens, PedersenGens, RangeProof};
+    use curve25519_dalek::ristretto::CompressedRistretto;
+    use curve25519_dalek::scalar::Scalar;
+    use merlin::Transcript;
+    use serde::{Deserialize, Serialize};
+    use std::error::Error;
+
+    // A single range proof covers at most 64 bits, so a u128 excess is proven as two 64-bit
+    // limbs in one aggregated proof
+    const LIMB_BITS: usize = 64;
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct BalanceGteProof {
+        pub threshold: u128,
+        // Commitment to the high limb of balance - threshold; the verifier derives the low one
+        pub high_commitment: [u8; 32],
+        pub proof: Vec<u8>,
+    }
+
+    fn transcript(threshold: u128) -> Transcript {
+        let mut transcript = Transcript::new(b"qudag-exchange balance_gte v2");
+        transcript.append_message(b"threshold", &threshold.to_le_bytes());
+        transcript
+    }
+
+    fn limb_shift() -> Scalar {
+        Scalar::from(1u128 << 64)
+    }
+
+    pub fn prove_balance_gte(balance: u128, threshold: u128, blinding: &Scalar) -> Result<BalanceGteProof, Box<dyn Error>> {
+        let excess = balance.checked_sub(threshold).ok_or("balance is below the threshold")?;
+        // blinding = low + 2^64 * high, so the limb commitments recombine into the excess
+        // commitment the verifier derives from the published balance commitment
+        let high_blinding = Scalar::random(&mut rand::thread_rng());
+        let low_blinding = blinding - limb_shift() * high_blinding;
+        let (proof, commitments) = RangeProof::prove_multiple(
+            &BulletproofGens::new(LIMB_BITS, 2),
+            &PedersenGens::default(),
+            &mut transcript(threshold),
+            &[excess as u64, (excess >> 64) as u64],
+            &[low_blinding, high_blinding],
+            LIMB_BITS,
+        )?;
+        // Serialized for over-the-wire use; the aggregated proof is under 800 bytes
+        Ok(BalanceGteProof { threshold, high_commitment: commitments[1].to_bytes(), proof: proof.to_bytes() })
+    }
+
+    pub fn verify_balance_proof(balance_commitment: &[u8; 32], proof: &BalanceGteProof) -> Result<(), Box<dyn Error>> {
+        // commit(balance - threshold, r) = commit(balance, r) - threshold * B, so the proof is
+        // checked against the published balance commitment, not one the prover picked
+        let gens = PedersenGens::default();
+        let balance_point = CompressedRistretto(*balance_commitment).decompress().ok_or("invalid balance commitment")?;
+        let excess_point = balance_point - Scalar::from(proof.threshold) * gens.B;
+        let high_point = CompressedRistretto(proof.high_commitment).decompress().ok_or("invalid limb commitment")?;
+        let low_point = excess_point - limb_shift() * high_point;
+
+        let range_proof = RangeProof::from_bytes(&proof.proof)?;
+        range_proof.verify_multiple(
+            &BulletproofGens::new(LIMB_BITS, 2),
+            &gens,
+            &mut transcript(proof.threshold),
+            &[low_point.compress(), high_point.compress()],
+            LIMB_BITS,
+        )?;
+        Ok(())
+    }
+
+    pub fn commit_balance(balance: u128, blinding: &Scalar) -> [u8; 32] {
+        // Confidential balance: the value published in place of a plaintext balance
+        PedersenGens::default().commit(Scalar::from(balance), *blinding).compress().to_bytes()
+    }
+}
+
+// Exchange API Server: REST and JSON-RPC
+// Lets exchanges and bots integrate over HTTP without linking Rust
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    routing::{get, post},
+    Json, Router,
+};
+
+struct ApiState {
+    exchange: Exchange,
+    api_keys: HashSet<String>,
+    // Requests seen per API key in the current one-minute window
+    rate_limits: Mutex<HashMap<String, (u64, u32)>>,
+    requests_per_minute: u32,
+    offers: tokio::sync::RwLock<OfferBook>,
+}
+
+fn api_router(state: std::sync::Arc<ApiState>) -> Router {
+    Router::new()
+        .route("/v1/accounts/:account/balance", get(get_balance))
+        .route("/v1/accounts/:account/nonce", get(get_nonce))
+        .route("/v1/transactions", post(submit_transaction))
+        .route("/v1/fees/estimate", post(estimate_fee_endpoint))
+        .route("/v1/market/search", post(search_market))
+        .route("/rpc", post(json_rpc))
+        .route("/v1/ws", get(websocket_upgrade))
+        .route("/openapi.json", get(|| async { generate_openapi_spec().unwrap_or_default() }))
+        .with_state(state)
+}
+
+fn authorize(state: &ApiState, headers: &HeaderMap) -> Result<(), (StatusCode, Json<ExchangeError>)> {
+    let key = headers.get("x-api-key").and_then(|v| v.to_str().ok()).unwrap_or("");
+    if !state.api_keys.contains(key) {
+        return Err((StatusCode::UNAUTHORIZED, Json(ExchangeError::new(ErrorCode::Unauthorized, "missing or unknown API key"))));
+    }
+
+    // Fixed one-minute windows per key
+    let minute = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs() / 60).unwrap_or(0);
+    let mut limits = state.rate_limits.lock().unwrap();
+    let (window, count) = limits.entry(key.to_string()).or_insert((minute, 0));
+    if *window != minute {
+        *window = minute;
+        *count = 0;
+    }
+    *count += 1;
+    if *count > state.requests_per_minute {
+        return Err((StatusCode::TOO_MANY_REQUESTS, Json(ExchangeError::new(ErrorCode::RateLimited, "rate limit exceeded"))));
+    }
+    Ok(())
+}
+
+fn api_error(error: Box<dyn Error>) -> (StatusCode, Json<ExchangeError>) {
+    let code = error_code_of(error.as_ref());
+    (StatusCode::from_u16(code.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR), Json(ExchangeError::new(code, error.to_string())))
+}
+
+async fn get_balance(State(state): State<std::sync::Arc<ApiState>>, headers: HeaderMap, Path(account): Path<String>) -> Result<Json<serde_json::Value>, (StatusCode, Json<ExchangeError>)> {
+    authorize(&state, &headers)?;
+    let ledger = state.exchange.ledger.read().await;
+    // Amounts are decimal strings, matching the WASM API
+    Ok(Json(serde_json::json!({ "account": account, "balance": balance(&ledger, &account, &AssetId(RUV.to_string())).to_string() })))
+}
+
+async fn get_nonce(State(state): State<std::sync::Arc<ApiState>>, headers: HeaderMap, Path(account): Path<String>) -> Result<Json<u64>, (StatusCode, Json<ExchangeError>)> {
+    authorize(&state, &headers)?;
+    Ok(Json(state.exchange.get_next_nonce(&account).await))
+}
+
+async fn submit_transaction(State(state): State<std::sync::Arc<ApiState>>, headers: HeaderMap, Json(tx): Json<ExchangeTransaction>) -> Result<Json<TransactionResult>, (StatusCode, Json<ExchangeError>)> {
+    authorize(&state, &headers)?;
+    Ok(Json(admit_submission(&state, tx).await.map_err(api_error)?))
+}
+
+// Shared by the REST and JSON-RPC submit paths
+async fn admit_submission(state: &ApiState, tx: ExchangeTransaction) -> Result<TransactionResult, Box<dyn Error>> {
+    verify_signature(&tx)?;
+    // The signed fee is what the ledger charges, so it must cover the fee model at admission time
+    let required = state.exchange.model_fee(&tx).await;
+    if tx.fee < required {
+        return Err(ExchangeError::new(ErrorCode::InvalidTransaction, format!("fee {} on {} is below the current fee of {}", tx.fee, tx.id, required)).into());
+    }
+    let tx_id = tx.id.clone();
+    let estimated_fee = {
+        let ledger = state.exchange.ledger.read().await;
+        let charged = tx.fee.saturating_add(memo_fee(&ledger.cost_model, &tx));
+        let mut mempool = state.exchange.mempool.write().await;
+        admit_to_mempool(&mut mempool, &ledger, tx)?;
+        charged
+    };
+    Ok(TransactionResult { tx_id, status: TransactionStatus::Pending, estimated_fee: Some(estimated_fee) })
+}
+
+async fn estimate_fee_endpoint(State(state): State<std::sync::Arc<ApiState>>, headers: HeaderMap, Json(tx): Json<ExchangeTransaction>) -> Result<Json<String>, (StatusCode, Json<ExchangeError>)> {
+    authorize(&state, &headers)?;
+    Ok(Json(state.exchange.estimate_fee(&tx).await.to_string()))
+}
+
+async fn search_market(State(state): State<std::sync::Arc<ApiState>>, headers: HeaderMap, Json(query): Json<ResourceQuery>) -> Result<Json<Vec<ResourceOffer>>, (StatusCode, Json<ExchangeError>)> {
+    authorize(&state, &headers)?;
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    Ok(Json(search_offers(&*state.offers.read().await, &HashMap::new(), &query, now)))
+}
+
+async fn json_rpc(State(state): State<std::sync::Arc<ApiState>>, headers: HeaderMap, Json(request): Json<serde_json::Value>) -> Result<Json<serde_json::Value>, (StatusCode, Json<ExchangeError>)> {
+    authorize(&state, &headers)?;
+    // JSON-RPC 2.0 mirror of the REST endpoints, for clients that prefer a single endpoint
+    let id = request["id"].clone();
+    let params = &request["params"];
+    let invalid_params = |e: serde_json::Error| serde_json::json!({ "code": -32602, "message": format!("invalid params: {}", e) });
+    // Exchange failures keep their ErrorCode in `data`, so RPC clients branch on it like REST ones
+    let failed = |e: Box<dyn Error>| serde_json::json!({ "code": -32000, "message": e.to_string(), "data": { "code": error_code_of(e.as_ref()) } });
+    let result = match request["method"].as_str() {
+        Some("exchange_getBalance") => {
+            let account = params[0].as_str().unwrap_or_default();
+            let ledger = state.exchange.ledger.read().await;
+            Ok(serde_json::json!(balance(&ledger, account, &AssetId(RUV.to_string())).to_string()))
+        }
+        Some("exchange_getNonce") => Ok(serde_json::json!(state.exchange.get_next_nonce(params[0].as_str().unwrap_or_default()).await)),
+        Some("exchange_submitTransaction") => match serde_json::from_value::<ExchangeTransaction>(params[0].clone()) {
+            Ok(tx) => admit_submission(&state, tx).await.map(|result| serde_json::json!(result)).map_err(failed),
+            Err(e) => Err(invalid_params(e)),
+        },
+        Some("exchange_estimateFee") => match serde_json::from_value::<ExchangeTransaction>(params[0].clone()) {
+            Ok(tx) => Ok(serde_json::json!(state.exchange.estimate_fee(&tx).await.to_string())),
+            Err(e) => Err(invalid_params(e)),
+        },
+        Some("exchange_searchMarket") => match serde_json::from_value::<ResourceQuery>(params[0].clone()) {
+            Ok(query) => {
+                let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+                Ok(serde_json::json!(search_offers(&*state.offers.read().await, &HashMap::new(), &query, now)))
+            }
+            Err(e) => Err(invalid_params(e)),
+        },
+        _ => Err(serde_json::json!({ "code": -32601, "message": "method not found" })),
+    };
+    Ok(Json(match result {
+        Ok(result) => serde_json::json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => serde_json::json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+    }))
+}
+
+// Exchange API: WebSocket Push
+// Dashboards subscribe to topics instead of polling get_stats()
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum PushTopic {
+    Transactions,
+    Confirmations,
+    Offers,
+    MarketStats,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum ClientCommand {
+    Subscribe { topics: Vec<PushTopic> },
+    Unsubscribe { topics: Vec<PushTopic> },
+}
+
+// Messages queued per connection before the client counts as too slow
+const WS_QUEUE_LIMIT: usize = 256;
+
+fn topic_of(event: &LedgerEvent) -> PushTopic {
+    match event {
+        LedgerEvent::StatusChanged { status: TransactionStatus::Finalized, .. } => PushTopic::Confirmations,
+        LedgerEvent::StatusChanged { .. } | LedgerEvent::Reorg { .. } => PushTopic::Transactions,
+        LedgerEvent::BalanceChanged { .. } => PushTopic::Transactions,
+    }
+}
+
+async fn websocket_upgrade(State(state): State<std::sync::Arc<ApiState>>, headers: HeaderMap, upgrade: WebSocketUpgrade) -> Result<axum::response::Response, (StatusCode, Json<ExchangeError>)> {
+    authorize(&state, &headers)?;
+    Ok(upgrade.on_upgrade(move |socket| push_events(state, socket)))
+}
+
+async fn push_events(state: std::sync::Arc<ApiState>, socket: WebSocket) {
+    use futures::SinkExt;
+
+    let (mut sender, mut receiver) = socket.split();
+    let mut topics: HashSet<PushTopic> = HashSet::new();
+    let mut events = Box::pin(state.exchange.events());
+    let (queue, mut outgoing) = tokio::sync::mpsc::channel::<String>(WS_QUEUE_LIMIT);
+
+    // Writer task: a slow socket only ever blocks this task, never the event loop below
+    let writer = tokio::spawn(async move {
+        while let Some(text) = outgoing.recv().await {
+            if sender.send(Message::Text(text)).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    loop {
+        tokio::select! {
+            command = receiver.next() => match command {
+                Some(Ok(Message::Text(text))) => match serde_json::from_str::<ClientCommand>(&text) {
+                    Ok(ClientCommand::Subscribe { topics: added }) => topics.extend(added),
+                    Ok(ClientCommand::Unsubscribe { topics: removed }) => removed.iter().for_each(|t| { topics.remove(t); }),
+                    Err(_) => {}
+                },
+                Some(Ok(_)) => {}
+                _ => break,
+            },
+            event = events.next() => {
+                let Some(event) = event else { break };
+                if !topics.contains(&topic_of(&event)) {
+                    continue;
+                }
+                let text = serde_json::json!({ "topic": topic_of(&event), "data": event }).to_string();
+                // Backpressure: a client whose queue is full is disconnected rather than buffered without bound
+                if queue.try_send(text).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+    writer.abort();
+}
+
+fn publish_market_update() -> Result<(), Box<dyn Error>> {
+    // Functionality to push Offers (created, amended, expired) and periodic MarketStats snapshots,
+    // once the market publishes through the same event channel as the ledger
+    Ok(())
+}
+
+// Exchange CLI: `qudag-exchange wallet`
+#[derive(Parser)]
+#[command(name = "qudag-exchange")]
+enum ExchangeCli {
+    /// Manage wallet accounts and keys
+    #[command(subcommand)]
+    Wallet(WalletCommand),
+    /// Sign transactions offline and broadcast them separately
+    #[command(subcommand)]
+    Tx(TxCommand),
+    /// Simulate fee model changes before proposing them
+    #[command(subcommand)]
+    Fees(FeesCommand),
+    /// Print a completion script for the given shell (bash, zsh, fish, powershell)
+    Completions { shell: Shell },
+    /// Print man pages, or write one per subcommand into a directory
+    Man {
+        #[arg(long)]
+        out_dir: Option<std::path::PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum OutputFormat {
+    Table,
+    Json,
+}
+
+#[derive(clap::Subcommand)]
+enum WalletCommand {
+    /// Create a new mnemonic and its first account; the seed is stored encrypted in the vault
+    Create {
+        /// Replace an existing vault; its seed is lost unless it was backed up
+        #[arg(long)]
+        force: bool,
+    },
+    /// Import accounts from a mnemonic or an exported keyfile
+    Import {
+        #[arg(long, conflicts_with = "keyfile")]
+        mnemonic: Option<String>,
+        #[arg(long)]
+        keyfile: Option<std::path::PathBuf>,
+        /// Replace an existing vault; its seed is lost unless it was backed up
+        #[arg(long)]
+        force: bool,
+    },
+    /// Export the seed as an encrypted keyfile
+    Export { out: std::path::PathBuf },
+    /// List accounts with their balances
+    List {
+        #[arg(long, value_enum, default_value = "table")]
+        format: OutputFormat,
+    },
+    /// Show an account address as text or a terminal QR code
+    Address {
+        index: u32,
+        #[arg(long)]
+        qr: bool,
+    },
+}
+
+fn run_wallet_command(command: WalletCommand, ledger: &Ledger, vault_path: &str, vault_secret: &[u8]) -> Result<(), Box<dyn Error>> {
+    let check_overwrite = |force: bool| -> Result<(), Box<dyn Error>> {
+        if !force && std::path::Path::new(vault_path).exists() {
+            return Err(format!("a vault already exists at {}; export it first, then pass --force to replace it", vault_path).into());
+        }
+        Ok(())
+    };
+
+    match command {
+        WalletCommand::Create { force } => {
+            check_overwrite(force)?;
+            let mnemonic = create_mnemonic()?;
+            store_seed_in_vault(&mnemonic, vault_path, vault_secret)?;
+            // Shown once so the user can write it down; it is never printed again
+            println!("{}", mnemonic);
+            println!("account 0: {}", account_id(&derive_account_key(&mnemonic, "", 0)?.public_key()));
+        }
+        WalletCommand::Import { mnemonic: Some(phrase), force, .. } => {
+            check_overwrite(force)?;
+            let mnemonic = bip39::Mnemonic::parse(phrase)?;
+            store_seed_in_vault(&mnemonic, vault_path, vault_secret)?;
+            for (index, account) in recover_accounts(ledger, &mnemonic, "", 20)? {
+                println!("recovered account {}: {}", index, account);
+            }
+        }
+        WalletCommand::Import { keyfile: Some(path), force, .. } => {
+            check_overwrite(force)?;
+            // Exported keyfiles use the vault file format, so importing is a copy
+            std::fs::copy(path, vault_path)?;
+        }
+        WalletCommand::Import { .. } => return Err("pass --mnemonic or --keyfile".into()),
+        WalletCommand::Export { out } => {
+            std::fs::copy(vault_path, out)?;
+        }
+        WalletCommand::List { format } => {
+            let mnemonic = load_seed_from_vault(vault_path, vault_secret)?;
+            // Every index up to the last used one, plus account 0, including empty accounts
+            let last_used = recover_accounts(ledger, &mnemonic, "", 20)?.last().map_or(0, |(index, _)| *index);
+            let accounts = (0..=last_used)
+                .map(|index| Ok((index, account_id(&derive_account_key(&mnemonic, "", index)?.public_key()))))
+                .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+            let rows: Vec<serde_json::Value> = accounts
+                .iter()
+                .map(|(index, account)| serde_json::json!({ "index": index, "account": account, "balance": balance(ledger, account, &AssetId(RUV.to_string())).to_string() }))
+                .collect();
+            match format {
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&rows)?),
+                OutputFormat::Table => {
+                    println!("{:<6} {:<64} {:>20}", "INDEX", "ACCOUNT", "BALANCE (rUv)");
+                    for row in rows {
+                        println!("{:<6} {:<64} {:>20}", row["index"], row["account"].as_str().unwrap_or(""), row["balance"].as_str().unwrap_or(""));
+                    }
+                }
+            }
+        }
+        WalletCommand::Address { index, qr } => {
+            let account = account_id(&derive_account_key(&load_seed_from_vault(vault_path, vault_secret)?, "", index)?.public_key());
+            if qr {
+                qr2term::print_qr(&account)?;
+            } else {
+                println!("{}", account);
+            }
+        }
+    }
+    Ok(())
+}
+
+// Exchange CLI: Offline Signing and Broadcast
+// `tx sign` runs on a cold machine with no network access; `tx broadcast` runs anywhere
+#[derive(clap::Subcommand)]
+enum TxCommand {
+    /// Build and sign a transfer, writing the signed blob to a file
+    Sign {
+        #[arg(long)]
+        account_index: u32,
+        #[arg(long)]
+        to: String,
+        #[arg(long)]
+        amount: u128,
+        /// Required: an offline machine can't look up the next nonce
+        #[arg(long)]
+        nonce: u64,
+        /// Fee-model fee from `/v1/fees/estimate`; nodes reject a blob that pays less than the current fee
+        #[arg(long, default_value_t = 0)]
+        fee: u128,
+        #[arg(long)]
+        valid_until: Option<u64>,
+        #[arg(long)]
+        out: std::path::PathBuf,
+    },
+    /// Submit a previously signed blob to a node
+    Broadcast {
+        blob: std::path::PathBuf,
+        #[arg(long, default_value = "http://localhost:8080")]
+        node: String,
+        #[arg(long, env = "QUDAG_API_KEY")]
+        api_key: String,
+    },
+}
+
+fn run_tx_command(command: TxCommand, vault_path: &str, vault_secret: &[u8]) -> Result<(), Box<dyn Error>> {
+    match command {
+        TxCommand::Sign { account_index, to, amount, nonce, fee, valid_until, out } => {
+            let mnemonic = load_seed_from_vault(vault_path, vault_secret)?;
+            let keypair = derive_account_key(&mnemonic, "", account_index)?;
+
+            let mut tx = ExchangeTransaction {
+                id: String::new(),
+                from: account_id(&keypair.public_key()),
+                to,
+                asset: AssetId(RUV.to_string()),
+                amount,
+                valid_until,
+                nonce,
+                fee,
+                kind: TransactionType::Transfer,
+                memo: None,
+                public_key: Vec::new(),
+                signature: Vec::new(),
+            };
+            sign_transaction(&mut tx, &keypair)?;
+            // The blob is plain JSON so it can be reviewed before it is carried to an online machine
+            std::fs::write(&out, serde_json::to_vec_pretty(&tx)?)?;
+            println!("signed {} -> {}", tx.id, out.display());
+        }
+        TxCommand::Broadcast { blob, node, api_key } => {
+            let tx: ExchangeTransaction = serde_json::from_slice(&std::fs::read(blob)?)?;
+            // Catch a damaged or edited blob before it reaches the node
+            verify_signature(&tx)?;
+            let response = reqwest::blocking::Client::new()
+                .post(format!("{}/v1/transactions", node))
+                .header("x-api-key", api_key)
+                .json(&tx)
+                .send()?;
+            if !response.status().is_success() {
+                let error: ExchangeError = response.json()?;
+                std::process::exit(error.code.exit_code());
+            }
+            let result: TransactionResult = response.json()?;
+            println!("submitted {} ({:?})", result.tx_id, result.status);
+        }
+    }
+    Ok(())
+}
+
+// Exchange Payouts: Batched Fee Distribution
+// Contributor shares accumulate in the FeeRouter and are paid in one FeeDistribution
+// transaction per run, instead of one transfer per contributor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PayoutSchedule {
+    interval_secs: u64,
+    // Shares below this are carried over to the next run to avoid dust payouts
+    minimum_payout: u128,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct FeeRouter {
+    accrued: HashMap<String, u128>,
+    last_run: u64,
+}
+
+fn accrue_share(router: &mut FeeRouter, contributor: &str, amount: u128) {
+    *router.accrued.entry(contributor.to_string()).or_default() += amount;
+}
+
+fn preview_payouts(router: &FeeRouter, schedule: &PayoutSchedule) -> Vec<(String, u128)> {
+    // Dry-run: the split the next run would pay, without changing anything
+    let mut payouts: Vec<(String, u128)> = router.accrued.iter().filter(|(_, amount)| **amount >= schedule.minimum_payout).map(|(a, v)| (a.clone(), *v)).collect();
+    payouts.sort();
+    payouts
+}
+
+fn run_payouts(router: &mut FeeRouter, schedule: &PayoutSchedule, fee_pool: &str, nonce: u64, now: u64) -> Option<ExchangeTransaction> {
+    if now < router.last_run + schedule.interval_secs {
+        return None;
+    }
+    router.last_run = now;
+    let payouts = preview_payouts(router, schedule);
+    if payouts.is_empty() {
+        return None;
+    }
+    // Shares stay accrued until confirm_payouts sees the distribution applied, so a rejected
+    // or lost transaction is paid again on the next run
+    // Signed by the fee pool's key before submission, like any other transaction
+    Some(ExchangeTransaction {
+        id: String::new(),
+        from: fee_pool.to_string(),
+        to: fee_pool.to_string(),
+        asset: AssetId(RUV.to_string()),
+        amount: payouts.iter().map(|(_, amount)| amount).sum(),
+        valid_until: None,
+        nonce,
+        fee: 0,
+        kind: TransactionType::FeeDistribution { payouts },
+        memo: None,
+        public_key: Vec::new(),
+        signature: Vec::new(),
+    })
+}
+
+fn confirm_payouts(router: &mut FeeRouter, payouts: &[(String, u128)]) {
+    // Called once the FeeDistribution is finalized; only the paid amount is removed, since
+    // more may have accrued while the transaction was in flight
+    for (account, paid) in payouts {
+        if let Some(accrued) = router.accrued.get_mut(account) {
+            *accrued = accrued.saturating_sub(*paid);
+            if *accrued == 0 {
+                router.accrued.remove(account);
+            }
+        }
+    }
+}
+
+fn distribute_fees(ledger: &mut Ledger, fee_pool: &str, payouts: &[(String, u128)], total: u128) -> Result<(), Box<dyn Error>> {
+    if payouts.iter().map(|(_, amount)| amount).sum::<u128>() != total {
+        return Err("fee distribution shares do not add up to its amount".into());
+    }
+    // All shares are paid or none. This runs inside apply_transaction, which already checked
+    // and bumps the pool's nonce, so the shares move as plain balance transfers
+    let ruv = AssetId(RUV.to_string());
+    let before = ledger.balances.clone();
+    for (account, amount) in payouts {
+        if let Err(e) = transfer_asset(ledger, fee_pool, account, &ruv, *amount) {
+            ledger.balances = before;
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+// Exchange: Proof-of-metering
+// Self-reported ResourceMetrics are only minted into rUv after the provider answers
+// sampled compute and storage challenges signed with its node key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ResourceMetrics {
+    provider: String,
+    // Contribution window the metrics cover; each window is minted at most once
+    #[serde(default)]
+    window: u64,
+    cpu_seconds: u64,
+    storage_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum MeteringChallenge {
+    // Compute a memory-hard hash chain from the seed; cost scales with claimed CPU
+    Compute { seed: [u8; 32], iterations: u64 },
+    // Return the hash of a randomly chosen chunk the provider claims to store
+    Storage { chunk_index: u64, nonce: [u8; 32] },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ChallengeResponse {
+    answer: [u8; 32],
+    // ML-DSA signature by the provider's node key over the challenge and answer
+    signature: Vec<u8>,
+}
+
+fn sample_challenges(metrics: &ResourceMetrics, sample_count: usize) -> Vec<MeteringChallenge> {
+    (0..sample_count)
+        .map(|i| {
+            if i % 2 == 0 && metrics.cpu_seconds > 0 {
+                MeteringChallenge::Compute { seed: rand::random(), iterations: (metrics.cpu_seconds / sample_count as u64).max(1) * 1_000 }
+            } else {
+                // 1 MiB chunks, chosen uniformly from the claimed storage
+                MeteringChallenge::Storage { chunk_index: rand::random::<u64>() % (metrics.storage_bytes >> 20).max(1), nonce: rand::random() }
+            }
+        })
+        .collect()
+}
+
+fn verify_challenge(challenge: &MeteringChallenge, response: &ChallengeResponse, provider_key: &MlDsaPublicKey, expected: impl Fn(&MeteringChallenge) -> [u8; 32]) -> bool {
+    let mut message = serde_json::to_vec(challenge).unwrap_or_default();
+    message.extend_from_slice(&response.answer);
+    // Compute answers are recomputed by the verifier; storage answers are checked against the
+    // chunk hashes committed when the data was uploaded
+    provider_key.verify(&message, &response.signature).is_ok() && response.answer == expected(challenge)
+}
+
+fn finalize_resource_contribution(ledger: &mut Ledger, metrics: &ResourceMetrics, passed: usize, total: usize, ruv_per_cpu_second: u128, oracle: &dyn QualityOracle) -> Result<u128, Box<dyn Error>> {
+    // With no challenges answered nothing was verified, so nothing is minted
+    if total == 0 {
+        return Err(format!("{} answered no metering challenges", metrics.provider).into());
+    }
+    if passed < total {
+        report_failed_challenges(metrics, total - passed)?;
+        return Err(format!("{} failed {} of {} metering challenges", metrics.provider, total - passed, total).into());
+    }
+    let contribution = format!("{}:{}", metrics.provider, metrics.window);
+    if ledger.minted_contributions.contains(&contribution) {
+        return Err(format!("{} window {} was already minted", metrics.provider, metrics.window).into());
+    }
+    // quality_score is clamped to [0, 1] and applied in basis points to keep minting integral
+    let quality_bps = (oracle.quality_score(&metrics.provider).clamp(0.0, 1.0) * 10_000.0) as u128;
+    let minted = metrics.cpu_seconds as u128 * ruv_per_cpu_second * quality_bps / 10_000;
+    let ruv = AssetId(RUV.to_string());
+    let provider_balance = balance(ledger, &metrics.provider, &ruv).checked_add(minted).ok_or("balance overflow")?;
+    ledger.balances.entry(metrics.provider.clone()).or_default().insert(ruv.clone(), provider_balance);
+    *ledger.supply.entry(ruv).or_default() += minted;
+    ledger.minted_contributions.insert(contribution);
+    Ok(minted)
+}
+
+fn report_failed_challenges(metrics: &ResourceMetrics, failures: usize) -> Result<(), Box<dyn Error>> {
+    // Slashing hook: failed challenges are reported like validator equivocation, with the
+    // signed responses attached so any node can check them
+    log_activity(&format!("{} failed {} metering challenges", metrics.provider, failures))
+}
+
+// Exchange: Quality-score Oracle
+// The minting formula's quality_score comes from a pluggable oracle instead of a constant
+trait QualityOracle {
+    // Score in [0, 1] for the provider's most recent contribution window
+    fn quality_score(&self, provider: &str) -> f64;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct QualitySample {
+    // Who produced the sample; a peer running benchmarks or a paying customer
+    reporter: String,
+    provider: String,
+    // Normalized to [0, 1]: benchmark throughput relative to the offered spec, or rating / 5
+    score: f64,
+    from_benchmark: bool,
+}
+
+// Default oracle: each sample is weighted by the reporter's reputation so a handful of new
+// accounts cannot rate a provider up or down
+#[derive(Debug)]
+struct ReputationWeightedOracle {
+    samples: HashMap<String, Vec<QualitySample>>,
+    reputation: HashMap<String, f64>,
+    // Share of the score taken from peer benchmarks; the rest comes from customer ratings
+    benchmark_weight: f64,
+    // Score for providers with no samples yet
+    default_score: f64,
+}
+
+impl Default for ReputationWeightedOracle {
+    fn default() -> Self {
+        ReputationWeightedOracle {
+            samples: HashMap::new(),
+            reputation: HashMap::new(),
+            // Benchmarks are measured, ratings are opinions, so benchmarks count for more
+            benchmark_weight: 0.7,
+            // New providers mint at half rate until someone has measured them, rather than nothing
+            default_score: 0.5,
+        }
+    }
+}
+
+impl ReputationWeightedOracle {
+    fn record(&mut self, sample: QualitySample) {
+        // Providers cannot rate themselves
+        if sample.reporter != sample.provider {
+            self.samples.entry(sample.provider.clone()).or_default().push(sample);
+        }
+    }
+
+    fn weighted_mean(&self, samples: &[&QualitySample]) -> Option<f64> {
+        let (sum, weight) = samples.iter().fold((0.0, 0.0), |(sum, weight), s| {
+            let w = self.reputation.get(&s.reporter).copied().unwrap_or(0.0).max(0.0);
+            (sum + s.score.clamp(0.0, 1.0) * w, weight + w)
+        });
+        (weight > 0.0).then(|| sum / weight)
+    }
+}
+
+impl QualityOracle for ReputationWeightedOracle {
+    fn quality_score(&self, provider: &str) -> f64 {
+        let samples = self.samples.get(provider).map(Vec::as_slice).unwrap_or_default();
+        let benchmarks: Vec<_> = samples.iter().filter(|s| s.from_benchmark).collect();
+        let ratings: Vec<_> = samples.iter().filter(|s| !s.from_benchmark).collect();
+        match (self.weighted_mean(&benchmarks), self.weighted_mean(&ratings)) {
+            (Some(b), Some(r)) => b * self.benchmark_weight + r * (1.0 - self.benchmark_weight),
+            (Some(score), None) | (None, Some(score)) => score,
+            (None, None) => self.default_score,
+        }
+    }
+}
+
+// Exchange Consensus Adapter: Transactions as DAG Vertices
+// Each submitted transaction becomes a vertex; QR-Avalanche finality is fed back to the ledger
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum FinalityStatus {
+    Unknown,
+    Pending { vertex_id: VertexId },
+    Confirmed { vertex_id: VertexId },
+    Rejected { vertex_id: VertexId, reason: String },
+}
+
+trait ConsensusInterface {
+    fn submit_transaction(&mut self, tx: ExchangeTransaction) -> Result<VertexId, Box<dyn Error>>;
+    fn get_finality_status(&self, tx_id: &str) -> FinalityStatus;
+}
+
+#[derive(Debug, Default)]
+struct ConsensusAdapter {
+    dag: Dag,
+    // Current tips; a new vertex references up to MAX_PARENTS of them
+    tips: HashSet<VertexId>,
+    payloads: HashMap<VertexId, ExchangeTransaction>,
+    vertex_of: HashMap<String, VertexId>,
+    finality: HashMap<VertexId, FinalityStatus>,
+}
+
+const MAX_PARENTS: usize = 8;
+
+// A vertex id commits to its payload and parents, so a peer cannot relabel either
+fn vertex_id_for(tx: &ExchangeTransaction, parents: &[VertexId]) -> Result<VertexId, Box<dyn Error>> {
+    // Sorted so the vertex id does not depend on HashSet iteration order
+    let mut sorted = parents.to_vec();
+    sorted.sort();
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&transaction_hash(tx)?);
+    sorted.iter().for_each(|p| {
+        hasher.update(p);
+    });
+    Ok(*hasher.finalize().as_bytes())
+}
+
+impl ConsensusInterface for ConsensusAdapter {
+    fn submit_transaction(&mut self, tx: ExchangeTransaction) -> Result<VertexId, Box<dyn Error>> {
+        if let Some(vertex_id) = self.vertex_of.get(&tx.id) {
+            return Ok(*vertex_id);
+        }
+        let mut parents: Vec<VertexId> = self.tips.iter().copied().collect();
+        parents.sort();
+        parents.truncate(MAX_PARENTS);
+        let vertex_id = vertex_id_for(&tx, &parents)?;
+
+        insert_batch(&mut self.dag, vec![NewVertex { id: vertex_id, parents: parents.clone() }])?;
+        parents.iter().for_each(|p| {
+            self.tips.remove(p);
+        });
+        self.tips.insert(vertex_id);
+        self.vertex_of.insert(tx.id.clone(), vertex_id);
+        self.finality.insert(vertex_id, FinalityStatus::Pending { vertex_id });
+        self.payloads.insert(vertex_id, tx);
+
+        broadcast_vertex(vertex_id)?;
+        Ok(vertex_id)
+    }
+
+    fn get_finality_status(&self, tx_id: &str) -> FinalityStatus {
+        self.vertex_of
+            .get(tx_id)
+            .and_then(|vertex_id| self.finality.get(vertex_id))
+            .cloned()
+            .unwrap_or(FinalityStatus::Unknown)
+    }
+}
+
+fn broadcast_vertex(vertex_id: VertexId) -> Result<(), Box<dyn Error>> {
+    // Functionality to gossip the vertex and start QR-Avalanche query rounds for it:
+    // each round queries sample_validators() through the network layer, and the vertex
+    // reaches a decision once enough consecutive rounds agree
+    log_activity(&format!("broadcast vertex {:x?}", &vertex_id[..4]))
+}
+
+impl Exchange {
+    async fn on_vertex_decided(&self, adapter: &mut ConsensusAdapter, vertex_id: VertexId, accepted: bool, now: u64) -> Result<(), Box<dyn Error>> {
+        let tx = adapter.payloads.get(&vertex_id).ok_or("decided vertex has no payload")?;
+        // A vertex is final once consensus accepts it; the transaction inside can still be
+        // rejected by the ledger, e.g. for a stale nonce
+        let (finality, status) = if !accepted {
+            let reason = "vertex rejected by consensus".to_string();
+            (FinalityStatus::Rejected { vertex_id, reason: reason.clone() }, TransactionStatus::Rejected(reason))
+        } else {
+            let mut ledger = self.ledger.write().await;
+            match apply_transaction(&mut ledger, tx, now) {
+                Ok(()) => (FinalityStatus::Confirmed { vertex_id }, TransactionStatus::Finalized),
+                Err(e) => (FinalityStatus::Confirmed { vertex_id }, TransactionStatus::Rejected(e.to_string())),
+            }
+        };
+        self.mempool.write().await.pending.remove(&tx.id);
+        self.publish(LedgerEvent::StatusChanged { tx_id: tx.id.clone(), status });
+        adapter.finality.insert(vertex_id, finality);
+        Ok(())
+    }
+}
+
+// Exchange: State Machine Replication from DAG Order
+// The ledger is a pure function of the confirmed DAG history, so any node can rebuild and audit it
+fn total_order(dag: &Dag, confirmed: &HashSet<VertexId>) -> Vec<VertexId> {
+    // Kahn's algorithm restricted to confirmed vertices; among ready vertices the smallest id
+    // goes first, so every node derives the same sequence
+    let mut remaining: HashMap<VertexId, usize> = confirmed
+        .iter()
+        .map(|v| (*v, dag.parents.get(v).map_or(0, |ps| ps.iter().filter(|p| confirmed.contains(*p)).count())))
+        .collect();
+    let mut children: HashMap<VertexId, Vec<VertexId>> = HashMap::new();
+    for v in confirmed {
+        for p in dag.parents.get(v).into_iter().flatten().filter(|p| confirmed.contains(*p)) {
+            children.entry(*p).or_default().push(*v);
+        }
+    }
+
+    let mut ready: std::collections::BTreeSet<VertexId> = remaining.iter().filter(|(_, n)| **n == 0).map(|(v, _)| *v).collect();
+    let mut order = Vec::with_capacity(confirmed.len());
+    while let Some(vertex) = ready.pop_first() {
+        order.push(vertex);
+        for child in children.get(&vertex).into_iter().flatten() {
+            let n = remaining.get_mut(child).expect("child is confirmed");
+            *n -= 1;
+            if *n == 0 {
+                ready.insert(*child);
+            }
+        }
+    }
+    order
+}
+
+#[derive(Debug, Default)]
+struct ReplicatedState {
+    ledger: Ledger,
+    // Applied vertices in application order. Append-only: a later confirmation with a smaller
+    // id goes after the applied prefix, never into it, so positions never shift
+    log: Vec<VertexId>,
+    applied: HashSet<VertexId>,
+    checkpoint_interval: u64,
+    // Position -> state hash, published so other nodes can compare without sharing full state
+    checkpoints: BTreeMap<u64, String>,
+}
+
+impl ReplicatedState {
+    fn apply_confirmed(&mut self, adapter: &ConsensusAdapter, confirmed: &HashSet<VertexId>, timestamp_of: impl Fn(&VertexId) -> u64) -> Result<(), Box<dyn Error>> {
+        // Only the newly confirmed vertices are ordered; total_order ignores parents outside the
+        // set, and those are already in the log
+        let new: HashSet<VertexId> = confirmed.difference(&self.applied).copied().collect();
+        for vertex in total_order(&adapter.dag, &new) {
+            self.apply_one(adapter, vertex, &timestamp_of)?;
+        }
+        Ok(())
+    }
+
+    fn apply_one(&mut self, adapter: &ConsensusAdapter, vertex: VertexId, timestamp_of: &impl Fn(&VertexId) -> u64) -> Result<(), Box<dyn Error>> {
+        let tx = adapter.payloads.get(&vertex).ok_or("confirmed vertex has no payload")?;
+        // `now` is the vertex's consensus timestamp, never the local clock, so expiry
+        // checks give the same answer on every replay. Ledger rejections are part of the
+        // deterministic outcome and are not errors here.
+        let _ = apply_transaction(&mut self.ledger, tx, timestamp_of(&vertex));
+        self.log.push(vertex);
+        self.applied.insert(vertex);
+        let position = self.log.len() as u64;
+        if self.checkpoint_interval > 0 && position % self.checkpoint_interval == 0 {
+            let hash = snapshot_hash(&self.ledger, &ValidatorSet::default(), &serde_json::Value::Null)?;
+            self.checkpoints.insert(position, hash);
+        }
+        Ok(())
+    }
+
+    fn checkpoint_snapshot(&self, storage: &mut dyn LedgerStorage) -> Result<(), Box<dyn Error>> {
+        storage.save_snapshot(self.log.len() as u64, &self.ledger)
+    }
+}
+
+fn rebuild_from_dag(genesis: Ledger, adapter: &ConsensusAdapter, log: &[VertexId], checkpoint_interval: u64, timestamp_of: impl Fn(&VertexId) -> u64) -> Result<ReplicatedState, Box<dyn Error>> {
+    // Replays a published log against the DAG; every entry must be a known vertex, and any
+    // parent that is itself in the log must come before it
+    let in_log: HashSet<&VertexId> = log.iter().collect();
+    let mut state = ReplicatedState { ledger: genesis, checkpoint_interval, ..Default::default() };
+    for vertex in log {
+        let parents = adapter.dag.parents.get(vertex).ok_or("log references an unknown vertex")?;
+        if let Some(parent) = parents.iter().find(|p| in_log.contains(p) && !state.applied.contains(*p)) {
+            return Err(format!("log applies {:x?} before its parent {:x?}", &vertex[..4], &parent[..4]).into());
+        }
+        if state.applied.contains(vertex) {
+            return Err(format!("log applies {:x?} twice", &vertex[..4]).into());
+        }
+        state.apply_one(adapter, *vertex, &timestamp_of)?;
+    }
+    Ok(state)
+}
+
+fn audit_against_checkpoints(rebuilt: &ReplicatedState, published: &BTreeMap<u64, String>) -> Result<(), Box<dyn Error>> {
+    // The first mismatching checkpoint bounds where the live ledger diverged from its history
+    for (position, hash) in published {
+        // A checkpoint the replay can't reproduce is unverified history, not a pass
+        if *position > rebuilt.log.len() as u64 {
+            return Err(format!("checkpoint at position {} is beyond the {}-entry rebuilt log", position, rebuilt.log.len()).into());
+        }
+        match rebuilt.checkpoints.get(position) {
+            Some(ours) if ours != hash => return Err(format!("state diverges from DAG history before position {}", position).into()),
+            Some(_) => {}
+            None => return Err(format!("no rebuilt checkpoint at position {} to compare against", position).into()),
+        }
+    }
+    Ok(())
+}
+
+// DAG: Checkpoints and Pruning
+// Vertices below a final checkpoint can be dropped from memory and exported to an archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum RetentionPolicy {
+    // Keep this many heights below the latest checkpoint
+    Depth(u64),
+    // Keep vertices newer than this many seconds
+    Time(u64),
+    // Keep the newest vertices up to this many bytes
+    Bytes(u64),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct VertexMeta {
+    height: u64,
+    timestamp: u64,
+    size_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DagCheckpoint {
+    height: u64,
+    // The final frontier: every vertex at or below the checkpoint is an ancestor of the cut
+    cut: Vec<VertexId>,
+    // Ledger state hash at this cut, from ReplicatedState
+    state_hash: String,
+}
+
+fn create_checkpoint(dag: &Dag, meta: &HashMap<VertexId, VertexMeta>, cut: Vec<VertexId>, is_final: impl Fn(&VertexId) -> bool, state_hash: String) -> Result<DagCheckpoint, Box<dyn Error>> {
+    // Only a cut of final vertices can be checkpointed; anything else could still be reordered
+    if let Some(pending) = cut.iter().find(|v| !dag.parents.contains_key(*v) || !is_final(v)) {
+        return Err(format!("vertex {:x?} in cut is not final", &pending[..4]).into());
+    }
+    let height = cut.iter().filter_map(|v| meta.get(v)).map(|m| m.height).min().ok_or("empty cut")?;
+    Ok(DagCheckpoint { height, cut, state_hash })
+}
+
+fn prune_below(dag: &mut Dag, meta: &mut HashMap<VertexId, VertexMeta>, checkpoint: &DagCheckpoint, policy: &RetentionPolicy, now: u64) -> Vec<(VertexId, Vec<VertexId>)> {
+    // Never prune above the checkpoint, whatever the policy allows
+    let below: HashSet<VertexId> = checkpoint.cut.iter().flat_map(|v| parallel_ancestors(dag, *v, usize::MAX)).collect();
+    let mut candidates: Vec<VertexId> = below.into_iter().filter(|v| meta.contains_key(v)).collect();
+    // Oldest first, so the Bytes policy drops the oldest vertices until the rest fits
+    candidates.sort_by_key(|v| (meta[v].height, *v));
+
+    let prunable: Vec<VertexId> = match policy {
+        RetentionPolicy::Depth(depth) => candidates.into_iter().filter(|v| meta[v].height + depth < checkpoint.height).collect(),
+        RetentionPolicy::Time(secs) => candidates.into_iter().filter(|v| meta[v].timestamp + secs < now).collect(),
+        RetentionPolicy::Bytes(limit) => {
+            let mut retained: u64 = meta.values().map(|m| m.size_bytes).sum();
+            candidates
+                .into_iter()
+                .take_while(|v| {
+                    let over = retained > *limit;
+                    retained -= meta[v].size_bytes;
+                    over
+                })
+                .collect()
+        }
+    };
+
+    let mut pruned = Vec::with_capacity(prunable.len());
+    for vertex in prunable {
+        dag.ancestor_filters.remove(&vertex);
+        meta.remove(&vertex);
+        if let Some(parents) = dag.parents.remove(&vertex) {
+            pruned.push((vertex, parents));
+        }
+    }
+    // Retained vertices may still list pruned parents; the checkpoint stands in for them
+    pruned
+}
+
+fn export_pruned_range(checkpoint: &DagCheckpoint, pruned: &[(VertexId, Vec<VertexId>)], path: &str) -> Result<(), Box<dyn Error>> {
+    // Archives are keyed by checkpoint height so the sync protocol can serve historical ranges
+    let archive = serde_json::json!({ "checkpoint": checkpoint, "vertices": pruned });
+    std::fs::write(format!("{}/dag-archive-{}.json", path, checkpoint.height), serde_json::to_vec(&archive)?)?;
+    Ok(())
+}
+
+// DAG Consensus: Async-native API
+// Calls share one runtime instead of building a fresh Runtime per call, which panicked when
+// called from async code
+#[derive(Debug, Clone, Default)]
+struct DagConsensusAsync {
+    dag: std::sync::Arc<tokio::sync::RwLock<Dag>>,
+    // Message hashes already in the DAG, for contains_message lookups without a traversal
+    messages: std::sync::Arc<tokio::sync::RwLock<HashMap<[u8; 32], VertexId>>>,
+    confirmed: std::sync::Arc<tokio::sync::RwLock<HashSet<VertexId>>>,
+}
+
+impl DagConsensusAsync {
+    async fn add_vertex_async(&self, vertex: NewVertex, message: &[u8]) -> Result<VertexId, Box<dyn Error>> {
+        let hash = *blake3::hash(message).as_bytes();
+        let mut dag = self.dag.write().await;
+        let id = insert_batch(&mut dag, vec![vertex])?[0];
+        // Indexed while the dag lock is still held, so readers never see the vertex without its message
+        self.messages.write().await.insert(hash, id);
+        Ok(id)
+    }
+
+    async fn get_total_order_async(&self) -> Vec<VertexId> {
+        let dag = self.dag.read().await;
+        total_order(&dag, &*self.confirmed.read().await)
+    }
+
+    async fn contains_message_async(&self, message: &[u8]) -> bool {
+        self.messages.read().await.contains_key(blake3::hash(message).as_bytes())
+    }
+}
+
+// Sync wrapper kept for existing callers; every call runs on one shared runtime
+struct DAGConsensus {
+    inner: DagConsensusAsync,
+    runtime: tokio::runtime::Handle,
+}
+
+fn shared_runtime() -> tokio::runtime::Handle {
+    static RUNTIME: std::sync::OnceLock<tokio::runtime::Runtime> = std::sync::OnceLock::new();
+    // Reuse the caller's runtime when there is one; otherwise start a single background runtime
+    tokio::runtime::Handle::try_current().unwrap_or_else(|_| {
+        RUNTIME.get_or_init(|| tokio::runtime::Builder::new_multi_thread().enable_all().build().expect("consensus runtime")).handle().clone()
+    })
+}
+
+impl DAGConsensus {
+    fn new() -> DAGConsensus {
+        DAGConsensus { inner: DagConsensusAsync::default(), runtime: shared_runtime() }
+    }
+
+    fn block_on<F>(&self, future: F) -> F::Output
+    where
+        F: std::future::Future + Send,
+        F::Output: Send,
+    {
+        use tokio::runtime::{Handle, RuntimeFlavor};
+        match Handle::try_current().map(|handle| handle.runtime_flavor()) {
+            // Inside a multi-threaded runtime, block_in_place hands this worker's other tasks to
+            // other threads rather than deadlocking the runtime
+            Ok(RuntimeFlavor::MultiThread) => tokio::task::block_in_place(|| self.runtime.block_on(future)),
+            // block_in_place panics on a current-thread runtime, and blocking its only thread
+            // would stall it, so the future is driven from a thread of its own
+            Ok(_) => std::thread::scope(|scope| {
+                scope.spawn(|| self.runtime.block_on(future)).join().unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            }),
+            Err(_) => self.runtime.block_on(future),
+        }
+    }
+
+    fn add_vertex(&self, vertex: NewVertex, message: &[u8]) -> Result<VertexId, Box<dyn Error>> {
+        self.block_on(self.inner.add_vertex_async(vertex, message))
+    }
+
+    fn get_total_order(&self) -> Vec<VertexId> {
+        self.block_on(self.inner.get_total_order_async())
+    }
+
+    fn contains_message(&self, message: &[u8]) -> bool {
+        self.block_on(self.inner.contains_message_async(message))
+    }
+}
+
+// DAG Consensus: QR-Avalanche Query Rounds
+// Vertices are no longer final on insert; they are decided by repeated sampled votes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AvalancheParams {
+    // Peers sampled per query round
+    k: usize,
+    // Votes out of k needed for a successful round
+    alpha: usize,
+    // Consecutive successful rounds needed to decide
+    beta: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum ConsensusStatus {
+    Pending,
+    Final,
+    Rejected,
+}
+
+#[derive(Debug, Default)]
+struct ConflictSet {
+    members: Vec<VertexId>,
+    preferred: Option<VertexId>,
+    last: Option<VertexId>,
+    // Consecutive successful rounds for `last`
+    count: u32,
+    // Set once a member is final; a decided set is never reopened
+    decided: Option<VertexId>,
+}
+
+#[derive(Debug, Default)]
+struct AvalancheState {
+    // Vertices spending the same (account, nonce) conflict; at most one of them can be final
+    conflicts: HashMap<(String, u64), ConflictSet>,
+    conflict_of: HashMap<VertexId, (String, u64)>,
+    // Total successful rounds per vertex; the preferred member is the one with the most
+    confidence: HashMap<VertexId, u64>,
+    status: HashMap<VertexId, ConsensusStatus>,
+}
+
+impl AvalancheState {
+    fn add_vertex(&mut self, vertex: VertexId, tx: &ExchangeTransaction) {
+        let key = (tx.from.clone(), tx.nonce);
+        let set = self.conflicts.entry(key.clone()).or_default();
+        set.members.push(vertex);
+        set.preferred.get_or_insert(vertex);
+        self.conflict_of.insert(vertex, key);
+        // A late conflicting spend can never win against an already final member
+        let status = if set.decided.is_some() { ConsensusStatus::Rejected } else { ConsensusStatus::Pending };
+        self.status.insert(vertex, status);
+    }
+
+    fn query_round(&mut self, vertex: VertexId, validators: &ValidatorSet, params: &AvalancheParams, query: impl Fn(&str, VertexId) -> bool) -> ConsensusStatus {
+        if self.status.get(&vertex) != Some(&ConsensusStatus::Pending) {
+            return self.status.get(&vertex).copied().unwrap_or(ConsensusStatus::Rejected);
+        }
+        let Some(key) = self.conflict_of.get(&vertex).cloned() else {
+            return ConsensusStatus::Rejected;
+        };
+        // Each sampled peer answers whether the vertex is its preferred member of the conflict set
+        let votes = sample_validators(validators, params.k).iter().filter(|peer| query(peer, vertex)).count();
+        let set = self.conflicts.get_mut(&key).expect("vertex has a conflict set");
+        if set.decided.is_some() {
+            self.status.insert(vertex, ConsensusStatus::Rejected);
+            return ConsensusStatus::Rejected;
+        }
+
+        if votes < params.alpha {
+            // Unsuccessful rounds reset the streak but never lower confidence
+            set.count = 0;
+            return ConsensusStatus::Pending;
+        }
+
+        let confidence = self.confidence.entry(vertex).or_default();
+        *confidence += 1;
+        let confidence = *confidence;
+        // Flip preference when a conflicting vertex overtakes the current one
+        let preferred = set.preferred.expect("conflict set has a member");
+        if preferred != vertex && confidence > self.confidence.get(&preferred).copied().unwrap_or(0) {
+            set.preferred = Some(vertex);
+        }
+        if set.last == Some(vertex) {
+            set.count += 1;
+        } else {
+            set.last = Some(vertex);
+            set.count = 1;
+        }
+
+        if set.count >= params.beta && set.preferred == Some(vertex) {
+            // Deciding one member rejects every other member of the conflict set
+            set.decided = Some(vertex);
+            for member in &set.members {
+                let status = if *member == vertex { ConsensusStatus::Final } else { ConsensusStatus::Rejected };
+                self.status.insert(*member, status);
+            }
+        }
+        self.status[&vertex]
+    }
+}
+
+fn run_query_rounds() -> Result<(), Box<dyn Error>> {
+    // Functionality to drive query_round for every pending vertex from the consensus task:
+    // queries go out through the network layer with a per-round timeout (a timed-out peer
+    // counts as a "no" vote), and decided vertices are passed to on_vertex_decided
+    Ok(())
+}
+
+// DAG Consensus: Byzantine Fault Injection
+// Simulated networks with adversarial validators, to check safety and liveness before release
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+enum ByzantineBehavior {
+    Honest,
+    // Votes yes for every member of a conflict set
+    Equivocate,
+    // Never answers queries; counted as a "no" like a timeout
+    WithholdVotes,
+    // Issues conflicting spends to different halves of the network, then backs whichever
+    // vertex the querying node does not prefer to keep the split alive
+    DoubleSpend,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ConsensusSimConfig {
+    honest: usize,
+    byzantine: Vec<ByzantineBehavior>,
+    params: AvalancheParams,
+    max_rounds: u32,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ConsensusSimReport {
+    // Honest nodes finalizing different members of the same conflict set
+    safety_violations: usize,
+    // Rounds until each honest node decided; missing entries never decided
+    rounds_to_decide: Vec<u32>,
+}
+
+fn conflicting_spend(id: &str) -> ExchangeTransaction {
+    ExchangeTransaction {
+        id: id.to_string(),
+        from: "attacker".to_string(),
+        to: format!("victim-{}", id),
+        asset: AssetId(RUV.to_string()),
+        amount: 1,
+        valid_until: None,
+        nonce: 0,
+        fee: 0,
+        kind: TransactionType::Transfer,
+        memo: None,
+        public_key: Vec::new(),
+        signature: Vec::new(),
+    }
+}
+
+fn simulate_byzantine_consensus(config: &ConsensusSimConfig) -> ConsensusSimReport {
+    let behaviors: Vec<ByzantineBehavior> = std::iter::repeat(ByzantineBehavior::Honest).take(config.honest).chain(config.byzantine.iter().copied()).collect();
+    let validators = ValidatorSet {
+        epoch: 0,
+        validators: (0..behaviors.len()).map(|i| Validator { peer_id: i.to_string(), stake: 1 }).collect(),
+    };
+    let (a, b) = ([0xa; 32], [0xb; 32]);
+
+    // The double spend reaches half the honest nodes first with `a` and the other half with `b`
+    let mut nodes: Vec<AvalancheState> = (0..config.honest)
+        .map(|i| {
+            let mut state = AvalancheState::default();
+            let (first, second) = if i % 2 == 0 { (a, b) } else { (b, a) };
+            state.add_vertex(first, &conflicting_spend(&format!("{:x}", first[0])));
+            state.add_vertex(second, &conflicting_spend(&format!("{:x}", second[0])));
+            state
+        })
+        .collect();
+    let key = ("attacker".to_string(), 0);
+    let mut decided: Vec<Option<(u32, VertexId)>> = vec![None; config.honest];
+
+    for round in 1..=config.max_rounds {
+        // Peers answer from their preferences at the start of the round
+        let preferred: Vec<Option<VertexId>> = nodes.iter().map(|n| n.conflicts[&key].preferred).collect();
+        for (i, node) in nodes.iter_mut().enumerate() {
+            if decided[i].is_some() {
+                continue;
+            }
+            let query = |peer: &str, vertex: VertexId| {
+                let peer: usize = peer.parse().expect("simulated peer ids are indices");
+                match behaviors[peer] {
+                    ByzantineBehavior::Honest => preferred[peer] == Some(vertex),
+                    ByzantineBehavior::Equivocate => true,
+                    ByzantineBehavior::WithholdVotes => false,
+                    ByzantineBehavior::DoubleSpend => preferred[i] != Some(vertex),
+                }
+            };
+            for vertex in [a, b] {
+                if node.query_round(vertex, &validators, &config.params, query) == ConsensusStatus::Final {
+                    decided[i] = Some((round, vertex));
+                }
+            }
+        }
+        if decided.iter().all(Option::is_some) {
+            break;
+        }
+    }
+
+    let finalized: HashSet<VertexId> = decided.iter().flatten().map(|(_, v)| *v).collect();
+    ConsensusSimReport {
+        safety_violations: finalized.len().saturating_sub(1),
+        rounds_to_decide: decided.iter().flatten().map(|(round, _)| *round).collect(),
+    }
+}
+
+fn check_consensus_under_faults(config: &ConsensusSimConfig) -> Result<(), Box<dyn Error>> {
+    let report = simulate_byzantine_consensus(config);
+    // Safety must hold for any number of faulty validators below a third of stake
+    if report.safety_violations > 0 {
+        return Err(format!("safety violated: honest nodes finalized {} conflicting vertices", report.safety_violations + 1).into());
+    }
+    // Liveness: every honest node decides within max_rounds
+    if report.rounds_to_decide.len() < config.honest {
+        return Err(format!("{} of {} honest nodes did not decide within {} rounds", config.honest - report.rounds_to_decide.len(), config.honest, config.max_rounds).into());
+    }
+    Ok(())
+}
+
+fn run_fault_injection_matrix() -> Result<(), Box<dyn Error>> {
+    // Functionality for the release checklist, run alongside tools/simulator scenarios:
+    // sweep each ByzantineBehavior at 10%, 20% and 30% of validators across k/alpha/beta
+    // presets and fail the release if check_consensus_under_faults fails for any combination
+    Ok(())
+}
+
+// DAG Sync: Catch-up for New Nodes
+// A fresh node learns the peer's tips, starts from its latest checkpoint, then pulls vertices
+// in height ranges until it reaches the tips
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum SyncRequest {
+    Tips,
+    LatestCheckpoint,
+    // Heights are inclusive; the response may be cut short at max_vertices
+    Range { from_height: u64, to_height: u64, max_vertices: usize },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum SyncResponse {
+    Tips { tips: Vec<VertexId>, max_height: u64 },
+    Checkpoint(Option<DagCheckpoint>),
+    Vertices(Vec<(NewVertex, VertexMeta, ExchangeTransaction)>),
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct SyncProgress {
+    synced_height: u64,
+    target_height: u64,
+    vertices_received: u64,
+    vertices_rejected: u64,
+}
+
+const SYNC_BATCH: usize = 512;
+
+fn sync_from_peer(
+    adapter: &mut ConsensusAdapter,
+    meta: &mut HashMap<VertexId, VertexMeta>,
+    cache: &VerificationCache,
+    mut request: impl FnMut(SyncRequest) -> Result<SyncResponse, Box<dyn Error>>,
+    mut on_progress: impl FnMut(&SyncProgress),
+) -> Result<SyncProgress, Box<dyn Error>> {
+    let SyncResponse::Tips { max_height, .. } = request(SyncRequest::Tips)? else {
+        return Err("peer answered tips request with another message".into());
+    };
+    let SyncResponse::Checkpoint(checkpoint) = request(SyncRequest::LatestCheckpoint)? else {
+        return Err("peer answered checkpoint request with another message".into());
+    };
+    // Starting at the checkpoint skips pruned history; the ledger is restored from the
+    // checkpoint's snapshot separately and checked against its state_hash
+    let mut progress = SyncProgress { synced_height: checkpoint.as_ref().map_or(0, |c| c.height), target_height: max_height, ..Default::default() };
+    // The cut stands in for pruned history: its vertices become roots for the ranges above it
+    for vertex in checkpoint.iter().flat_map(|c| &c.cut) {
+        adapter.dag.parents.entry(*vertex).or_default();
+    }
+
+    while progress.synced_height < progress.target_height {
+        let SyncResponse::Vertices(batch) = request(SyncRequest::Range {
+            from_height: progress.synced_height + 1,
+            to_height: progress.target_height,
+            max_vertices: SYNC_BATCH,
+        })?
+        else {
+            return Err("peer answered range request with another message".into());
+        };
+        if batch.is_empty() {
+            return Err(format!("peer has no vertices above height {}", progress.synced_height).into());
+        }
+        progress.vertices_received += batch.len() as u64;
+        let truncated = batch.len() >= SYNC_BATCH;
+
+        for (vertex, _, tx) in &batch {
+            if vertex_id_for(tx, &vertex.parents)? != vertex.id {
+                progress.vertices_rejected += 1;
+                return Err(format!("peer served vertex {:x?} whose id does not match its payload", &vertex.id[..4]).into());
+            }
+        }
+
+        // Signatures for the whole batch are checked in parallel before anything is inserted
+        let txs: Vec<ExchangeTransaction> = batch.iter().map(|(_, _, tx)| tx.clone()).collect();
+        let valid: HashSet<String> = verify_incoming(cache, txs)?.into_iter().map(|tx| tx.id).collect();
+        let (accepted, rejected): (Vec<_>, Vec<_>) = batch.into_iter().partition(|(_, _, tx)| valid.contains(&tx.id));
+        progress.vertices_rejected += rejected.len() as u64;
+        if !rejected.is_empty() {
+            // A peer serving invalid history is not trusted for the rest of the sync
+            return Err(format!("peer served {} vertices with invalid signatures", rejected.len()).into());
+        }
+
+        // A truncated batch may hold only part of its highest height; that height is
+        // requested again, so only the heights below it are kept
+        let top = accepted.iter().map(|(_, m, _)| m.height).max().unwrap_or(progress.synced_height);
+        let complete = if truncated { top.saturating_sub(1) } else { top };
+        if complete <= progress.synced_height {
+            return Err(format!("height {} has more than {} vertices", top, SYNC_BATCH).into());
+        }
+        let accepted: Vec<_> = accepted.into_iter().filter(|(_, m, _)| m.height <= complete).collect();
+        let vertices = accepted.iter().map(|(v, _, _)| v.clone()).collect();
+        insert_batch(&mut adapter.dag, vertices)?;
+        for (vertex, vertex_meta, tx) in accepted {
+            adapter.vertex_of.insert(tx.id.clone(), vertex.id);
+            adapter.payloads.insert(vertex.id, tx);
+            meta.insert(vertex.id, vertex_meta);
+        }
+        progress.synced_height = complete;
+        on_progress(&progress);
+    }
+    Ok(progress)
+}
+
+fn pipeline_sync() -> Result<(), Box<dyn Error>> {
+    // Functionality to request range N+1 while range N is being verified and range N-1
+    // inserted, same staging as pipeline_insertion; ranges are spread across several peers
+    // and re-requested from another peer on timeout
+    Ok(())
+}
+
+// DAG: Vertex Validation Pipeline
+// Gossip delivers the same vertex many times; each is verified once, across all cores
+// A few MB of ids; far more than the vertices gossip redelivers within a round
+const VALIDATED_CAPACITY: usize = 100_000;
+
+#[derive(Debug)]
+struct BoundedSeen {
+    capacity: usize,
+    // Insertion order, oldest first, for eviction
+    order: VecDeque<VertexId>,
+    seen: HashSet<VertexId>,
+}
+
+impl Default for BoundedSeen {
+    fn default() -> Self {
+        // A zero capacity would evict every vertex as soon as it was inserted
+        BoundedSeen { capacity: VALIDATED_CAPACITY, order: VecDeque::new(), seen: HashSet::new() }
+    }
+}
+
+impl BoundedSeen {
+    fn insert(&mut self, vertex: VertexId) {
+        if self.seen.insert(vertex) {
+            self.order.push_back(vertex);
+        }
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ValidationPipeline {
+    // Vertices that already passed validation; bounded so memory stays flat on long-running validators
+    validated: Mutex<BoundedSeen>,
+}
+
+impl Default for ValidationPipeline {
+    fn default() -> Self {
+        ValidationPipeline::with_capacity(VALIDATED_CAPACITY)
+    }
+}
+
+impl ValidationPipeline {
+    fn with_capacity(capacity: usize) -> ValidationPipeline {
+        ValidationPipeline { validated: Mutex::new(BoundedSeen { capacity, ..Default::default() }) }
+    }
+
+    fn validate(&self, batch: Vec<(NewVertex, ExchangeTransaction)>) -> Vec<(NewVertex, ExchangeTransaction)> {
+        // Drop vertices seen before and duplicates within the batch before any crypto runs
+        let mut fresh = Vec::with_capacity(batch.len());
+        {
+            let validated = self.validated.lock().unwrap();
+            let mut in_batch = HashSet::new();
+            for (vertex, tx) in batch {
+                if !validated.seen.contains(&vertex.id) && in_batch.insert(vertex.id) {
+                    fresh.push((vertex, tx));
+                }
+            }
+        }
+
+        // ML-DSA verification dominates ingest cost and is independent per vertex. The id must
+        // commit to this payload and these parents, or a peer could relabel a validly signed transaction
+        let valid: Vec<(NewVertex, ExchangeTransaction)> = fresh
+            .into_par_iter()
+            .filter(|(vertex, tx)| vertex_id_for(tx, &vertex.parents).map_or(false, |id| id == vertex.id) && verify_signature(tx).is_ok())
+            .collect();
+
+        // Only successes are remembered, so a vertex that failed can be retried if it was
+        // merely received with a corrupted payload
+        let mut validated = self.validated.lock().unwrap();
+        valid.iter().for_each(|(vertex, _)| validated.insert(vertex.id));
+        valid
+    }
+
+    async fn validate_async(self: std::sync::Arc<Self>, batch: Vec<(NewVertex, ExchangeTransaction)>) -> Result<Vec<(NewVertex, ExchangeTransaction)>, Box<dyn Error>> {
+        // Keeps the rayon work off the Tokio worker threads that serve the network
+        Ok(tokio::task::spawn_blocking(move || self.validate(batch)).await?)
+    }
+}
+
+fn benchmark_validation_pipeline() -> Result<(), Box<dyn Error>> {
+    // Functionality to measure vertices/sec at 1, 4, 8 and 16 rayon threads with 0%, 50% and
+    // 90% duplicate rates, against the single-threaded path
+    Ok(())
+}
+
+// Network: Transport-backed NetworkManager
+// connect_peer and send_message go through the libp2p P2PNode swarm task instead of
+// inventing peer ids and logging
+#[derive(Debug)]
+enum NetworkError {
+    DialFailed { addr: String, reason: String },
+    NotConnected(String),
+    Timeout(String),
+    // The remote read the message but refused it, e.g. failed validation
+    Rejected { peer: String, reason: String },
+    SwarmStopped,
+}
+
+impl std::fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            NetworkError::DialFailed { addr, reason } => write!(f, "dial {} failed: {}", addr, reason),
+            NetworkError::NotConnected(peer) => write!(f, "not connected to {}", peer),
+            NetworkError::Timeout(peer) => write!(f, "no acknowledgement from {}", peer),
+            NetworkError::Rejected { peer, reason } => write!(f, "{} rejected message: {}", peer, reason),
+            NetworkError::SwarmStopped => write!(f, "network swarm is not running"),
+        }
+    }
+}
+
+impl Error for NetworkError {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MessageAck {
+    peer: String,
+    // blake3 of the delivered bytes, echoed back by the remote
+    message_hash: [u8; 32],
+}
+
+// Commands handled by the swarm task that owns the libp2p P2PNode
+#[derive(Debug)]
+enum SwarmCommand {
+    Dial { addr: String, reply: tokio::sync::oneshot::Sender<Result<String, NetworkError>> },
+    Send { peer: String, protocol: String, bytes: Vec<u8>, reply: tokio::sync::oneshot::Sender<Result<MessageAck, NetworkError>> },
+}
+
+#[derive(Debug, Clone)]
+struct NetworkManager {
+    local_peer_id: String,
+    commands: tokio::sync::mpsc::Sender<SwarmCommand>,
+    // Peer id -> addresses it was reached on
+    connected: std::sync::Arc<tokio::sync::RwLock<HashMap<String, Vec<String>>>>,
+    ack_timeout: std::time::Duration,
+    traffic: std::sync::Arc<Mutex<TrafficAccounting>>,
+}
+
+impl NetworkManager {
+    async fn connect_peer(&self, addr: &str) -> Result<String, NetworkError> {
+        let (reply, rx) = tokio::sync::oneshot::channel();
+        self.commands.send(SwarmCommand::Dial { addr: addr.to_string(), reply }).await.map_err(|_| NetworkError::SwarmStopped)?;
+        // The peer id comes from the remote's authenticated identity, not from the address
+        let peer = rx.await.map_err(|_| NetworkError::SwarmStopped)??;
+        self.connected.write().await.entry(peer.clone()).or_default().push(addr.to_string());
+        Ok(peer)
+    }
+
+    async fn send_message(&self, peer: &str, protocol: &str, bytes: Vec<u8>) -> Result<MessageAck, NetworkError> {
+        if !self.connected.read().await.contains_key(peer) {
+            return Err(NetworkError::NotConnected(peer.to_string()));
+        }
+        let expected = *blake3::hash(&bytes).as_bytes();
+        self.traffic.lock().unwrap().record(peer, protocol, Direction::Outbound, bytes.len() as u64);
+        let (reply, rx) = tokio::sync::oneshot::channel();
+        let command = SwarmCommand::Send { peer: peer.to_string(), protocol: protocol.to_string(), bytes, reply };
+        self.commands.send(command).await.map_err(|_| NetworkError::SwarmStopped)?;
+
+        let ack = tokio::time::timeout(self.ack_timeout, rx)
+            .await
+            .map_err(|_| NetworkError::Timeout(peer.to_string()))?
+            .map_err(|_| NetworkError::SwarmStopped)??;
+        if ack.message_hash != expected {
+            return Err(NetworkError::Rejected { peer: peer.to_string(), reason: "acknowledged a different message".to_string() });
+        }
+        Ok(ack)
+    }
+}
+
+fn run_swarm() -> Result<(), Box<dyn Error>> {
+    // Functionality for the swarm task that owns the P2PNode:
+    // - Dial: swarm.dial(multiaddr); reply with the PeerId once the handshake completes
+    // - Send: reuse the open substream for (peer, protocol) or open one, write a
+    //   length-prefixed frame, and reply when the remote's ack frame arrives
+    // - A closed substream is reopened once before the send fails with NotConnected
+    Ok(())
+}
+
+// Network: Gossipsub Propagation
+// Transactions and vertices travel on their own topics; invalid or duplicate floods cost the
+// sender peer score
+const TOPIC_TRANSACTIONS: &str = "/qudag/exchange/tx/1";
+const TOPIC_VERTICES: &str = "/qudag/dag/vertex/1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+enum GossipVerdict {
+    // Deliver locally and forward
+    Accept,
+    // Drop silently; not the sender's fault, e.g. a stale nonce
+    Ignore,
+    // Drop and penalize the sender
+    Reject,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PeerScoreParams {
+    invalid_message_penalty: f64,
+    duplicate_penalty: f64,
+    // Mesh peers forward duplicates by design; they are only penalized past this many per seen window
+    mesh_duplicate_cap: u32,
+    first_delivery_reward: f64,
+    // Peers below this score are pruned from the mesh and their messages dropped
+    graylist_threshold: f64,
+}
+
+struct Gossip {
+    // Message id -> first-seen time, for deduplication within the seen window
+    seen: HashMap<[u8; 32], u64>,
+    seen_window_secs: u64,
+    validators: HashMap<String, Box<dyn Fn(&[u8]) -> GossipVerdict + Send + Sync>>,
+    scores: HashMap<String, f64>,
+    params: PeerScoreParams,
+    // Peers in our mesh for any topic
+    mesh: HashSet<String>,
+    // Per peer: start of the current seen window and duplicates delivered in it
+    duplicates: HashMap<String, (u64, u32)>,
+}
+
+fn message_id(topic: &str, data: &[u8]) -> [u8; 32] {
+    // Content addressed, so the same transaction from two peers is one message
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(topic.as_bytes());
+    hasher.update(data);
+    *hasher.finalize().as_bytes()
+}
+
+impl Gossip {
+    fn register_validator(&mut self, topic: &str, validate: impl Fn(&[u8]) -> GossipVerdict + Send + Sync + 'static) {
+        self.validators.insert(topic.to_string(), Box::new(validate));
+    }
+
+    fn on_message(&mut self, from: &str, topic: &str, data: &[u8], now: u64) -> GossipVerdict {
+        if self.scores.get(from).copied().unwrap_or(0.0) < self.params.graylist_threshold {
+            return GossipVerdict::Ignore;
+        }
+        self.seen.retain(|_, first_seen| *first_seen + self.seen_window_secs > now);
+
+        let id = message_id(topic, data);
+        let score = self.scores.entry(from.to_string()).or_default();
+        if self.seen.contains_key(&id) {
+            let (window_start, count) = self.duplicates.entry(from.to_string()).or_insert((now, 0));
+            if *window_start + self.seen_window_secs <= now {
+                (*window_start, *count) = (now, 0);
+            }
+            *count += 1;
+            if !self.mesh.contains(from) || *count > self.params.mesh_duplicate_cap {
+                *score -= self.params.duplicate_penalty;
+            }
+            return GossipVerdict::Ignore;
+        }
+        self.seen.insert(id, now);
+
+        // Unknown topics are rejected; peers should only publish what we subscribed to
+        let verdict = self.validators.get(topic).map_or(GossipVerdict::Reject, |validate| validate(data));
+        match verdict {
+            GossipVerdict::Accept => *score += self.params.first_delivery_reward,
+            GossipVerdict::Reject => *score -= self.params.invalid_message_penalty,
+            GossipVerdict::Ignore => {}
+        }
+        verdict
+    }
+}
+
+fn install_exchange_validators(gossip: &mut Gossip, pipeline: std::sync::Arc<ValidationPipeline>) {
+    gossip.register_validator(TOPIC_TRANSACTIONS, |data| match serde_json::from_slice::<ExchangeTransaction>(data) {
+        Ok(tx) if verify_signature(&tx).is_ok() => GossipVerdict::Accept,
+        _ => GossipVerdict::Reject,
+    });
+    gossip.register_validator(TOPIC_VERTICES, move |data| match serde_json::from_slice::<(NewVertex, ExchangeTransaction)>(data) {
+        // Already validated earlier; forwarding again would only add duplicates
+        Ok((vertex, _)) if pipeline.validated.lock().unwrap().seen.contains(&vertex.id) => GossipVerdict::Ignore,
+        Ok(vertex) if !pipeline.validate(vec![vertex]).is_empty() => GossipVerdict::Accept,
+        _ => GossipVerdict::Reject,
+    });
+}
+
+// Network: Persistent Identity and Peerstore
+// The node keeps its PeerId across restarts and remembers which peers were worth talking to
+fn load_or_create_identity(data_dir: &str, vault_secret: &[u8]) -> Result<libp2p::identity::Keypair, Box<dyn Error>> {
+    let path = format!("{}/node_key", data_dir);
+    if std::path::Path::new(&path).exists() {
+        let file: EncryptedStateFile = serde_json::from_slice(&std::fs::read(&path)?)?;
+        return Ok(libp2p::identity::Keypair::from_protobuf_encoding(&decrypt_state(&file, vault_secret)?)?);
+    }
+    // First start: generate once and keep it under the same vault-derived encryption as other state
+    let keypair = libp2p::identity::Keypair::generate_ed25519();
+    write_encrypted_state(&path, &keypair.to_protobuf_encoding()?, vault_secret, 1)?;
+    Ok(keypair)
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct PeerRecord {
+    addrs: Vec<String>,
+    protocols: Vec<String>,
+    reputation: f64,
+    last_connected: u64,
+    failed_dials: u32,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Peerstore {
+    peers: HashMap<String, PeerRecord>,
+}
+
+impl Peerstore {
+    fn load(data_dir: &str, vault_secret: &[u8]) -> Result<Peerstore, Box<dyn Error>> {
+        let path = format!("{}/peerstore", data_dir);
+        if !std::path::Path::new(&path).exists() {
+            return Ok(Peerstore::default());
+        }
+        let file: EncryptedStateFile = serde_json::from_slice(&std::fs::read(&path)?)?;
+        Ok(serde_json::from_slice(&decrypt_state(&file, vault_secret)?)?)
+    }
+
+    fn save(&self, data_dir: &str, vault_secret: &[u8]) -> Result<(), Box<dyn Error>> {
+        write_encrypted_state(&format!("{}/peerstore", data_dir), &serde_json::to_vec(self)?, vault_secret, 1)
+    }
+
+    fn record_connected(&mut self, peer: &str, addr: &str, protocols: Vec<String>, now: u64) {
+        let record = self.peers.entry(peer.to_string()).or_default();
+        if !record.addrs.iter().any(|a| a == addr) {
+            record.addrs.push(addr.to_string());
+        }
+        record.protocols = protocols;
+        record.last_connected = now;
+        record.failed_dials = 0;
+    }
+
+    fn redial_candidates(&self, min_reputation: f64, max_failed_dials: u32, limit: usize) -> Vec<(String, Vec<String>)> {
+        // Best reputation first, most recently seen breaking ties
+        let mut good: Vec<_> = self.peers.iter().filter(|(_, r)| r.reputation >= min_reputation && r.failed_dials < max_failed_dials).collect();
+        good.sort_by(|a, b| b.1.reputation.total_cmp(&a.1.reputation).then(b.1.last_connected.cmp(&a.1.last_connected)));
+        good.into_iter().take(limit).map(|(peer, r)| (peer.clone(), r.addrs.clone())).collect()
+    }
+}
+
+async fn redial_known_peers(network: &NetworkManager, peerstore: &mut Peerstore) {
+    // Runs at startup before bootstrap peers are dialed, so a restart rejoins the same neighborhood
+    for (peer, addrs) in peerstore.redial_candidates(0.0, 5, 32) {
+        let mut connected = false;
+        for addr in &addrs {
+            if network.connect_peer(addr).await.is_ok() {
+                connected = true;
+                break;
+            }
+        }
+        if !connected {
+            if let Some(record) = peerstore.peers.get_mut(&peer) {
+                record.failed_dials += 1;
+            }
+        }
+    }
+}
+
+// Network: Bandwidth Accounting and Rate Limits
+// Bytes are counted per peer and protocol; inbound traffic over a peer's budget is throttled,
+// and persistent offenders are disconnected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+enum Direction {
+    Inbound,
+    Outbound,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RateLimitConfig {
+    // Token bucket per peer: sustained bytes/sec and the burst it may accumulate
+    bytes_per_sec: u64,
+    burst_bytes: u64,
+    // Throttled messages allowed per minute before the peer is disconnected
+    max_violations_per_min: u32,
+}
+
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    tokens: f64,
+    last_refill: std::time::Instant,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+enum ThrottleAction {
+    Allow,
+    // Drop this message and stop reading from the peer until the bucket refills
+    Throttle,
+    Disconnect,
+}
+
+#[derive(Debug, Default)]
+struct TrafficAccounting {
+    config: Option<RateLimitConfig>,
+    bytes: HashMap<(String, String, Direction), u64>,
+    buckets: HashMap<String, TokenBucket>,
+    // Violation timestamps in the last minute, per peer
+    violations: HashMap<String, VecDeque<std::time::Instant>>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct NetworkStats {
+    connected_peers: usize,
+    bytes_in: u64,
+    bytes_out: u64,
+    bytes_by_protocol: HashMap<String, (u64, u64)>,
+    throttled_peers: usize,
+}
+
+impl TrafficAccounting {
+    fn record(&mut self, peer: &str, protocol: &str, direction: Direction, bytes: u64) {
+        *self.bytes.entry((peer.to_string(), protocol.to_string(), direction)).or_default() += bytes;
+    }
+
+    fn on_inbound(&mut self, peer: &str, protocol: &str, bytes: u64) -> ThrottleAction {
+        self.record(peer, protocol, Direction::Inbound, bytes);
+        let Some(config) = &self.config else {
+            return ThrottleAction::Allow;
+        };
+
+        let now = std::time::Instant::now();
+        let bucket = self.buckets.entry(peer.to_string()).or_insert(TokenBucket { tokens: config.burst_bytes as f64, last_refill: now });
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * config.bytes_per_sec as f64).min(config.burst_bytes as f64);
+        bucket.last_refill = now;
+        if bucket.tokens >= bytes as f64 {
+            bucket.tokens -= bytes as f64;
+            return ThrottleAction::Allow;
+        }
+
+        let violations = self.violations.entry(peer.to_string()).or_default();
+        violations.retain(|at| now.duration_since(*at) < std::time::Duration::from_secs(60));
+        violations.push_back(now);
+        if violations.len() as u32 > config.max_violations_per_min {
+            ThrottleAction::Disconnect
+        } else {
+            ThrottleAction::Throttle
+        }
+    }
+
+    fn forget_peer(&mut self, peer: &str) {
+        // Byte totals are kept for stats; only the limiter state goes away
+        self.buckets.remove(peer);
+        self.violations.remove(peer);
+    }
+}
+
+impl NetworkManager {
+    async fn get_network_stats(&self) -> NetworkStats {
+        let connected_peers = self.connected.read().await.len();
+        let traffic = self.traffic.lock().unwrap();
+        let mut stats = NetworkStats { connected_peers, ..Default::default() };
+        for ((_, protocol, direction), bytes) in &traffic.bytes {
+            let entry = stats.bytes_by_protocol.entry(protocol.clone()).or_default();
+            match direction {
+                Direction::Inbound => {
+                    stats.bytes_in += bytes;
+                    entry.0 += bytes;
+                }
+                Direction::Outbound => {
+                    stats.bytes_out += bytes;
+                    entry.1 += bytes;
+                }
+            }
+        }
+        stats.throttled_peers = traffic.violations.values().filter(|v| !v.is_empty()).count();
+        stats
+    }
+}
+
+// Network: Reputation Policy Engine
+// ReputationManager delegates scoring, decay and thresholds to a policy instead of hard-coded deltas
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+enum ReputationEvent {
+    InvalidMessage,
+    Timeout,
+    UsefulData { bytes: u64 },
+    // Seconds of continuous uptime since the last report
+    Uptime { secs: u64 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum PeerStanding {
+    Trusted,
+    // Still connected, but not used for queries or relaying until the score recovers
+    Greylisted,
+    Blacklisted,
+}
+
+trait ReputationPolicy: Send + Sync {
+    fn score_delta(&self, event: &ReputationEvent) -> f64;
+    // Applied to a score after `elapsed_secs` without events
+    fn decay(&self, score: f64, elapsed_secs: u64) -> f64;
+    fn standing(&self, score: f64) -> PeerStanding;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DefaultReputationPolicy {
+    invalid_message: f64,
+    timeout: f64,
+    per_useful_mib: f64,
+    per_uptime_hour: f64,
+    // Scores move halfway back towards zero every half_life_secs
+    half_life_secs: u64,
+    greylist_below: f64,
+    blacklist_below: f64,
+}
+
+impl ReputationPolicy for DefaultReputationPolicy {
+    fn score_delta(&self, event: &ReputationEvent) -> f64 {
+        match event {
+            ReputationEvent::InvalidMessage => -self.invalid_message,
+            ReputationEvent::Timeout => -self.timeout,
+            ReputationEvent::UsefulData { bytes } => *bytes as f64 / (1 << 20) as f64 * self.per_useful_mib,
+            ReputationEvent::Uptime { secs } => *secs as f64 / 3600.0 * self.per_uptime_hour,
+        }
+    }
+
+    fn decay(&self, score: f64, elapsed_secs: u64) -> f64 {
+        score * 0.5f64.powf(elapsed_secs as f64 / self.half_life_secs.max(1) as f64)
+    }
+
+    fn standing(&self, score: f64) -> PeerStanding {
+        if score < self.blacklist_below {
+            PeerStanding::Blacklisted
+        } else if score < self.greylist_below {
+            PeerStanding::Greylisted
+        } else {
+            PeerStanding::Trusted
+        }
+    }
+}
+
+struct ReputationManager {
+    policy: Box<dyn ReputationPolicy>,
+    // Peer -> (score, time of last update)
+    scores: HashMap<String, (f64, u64)>,
+}
+
+impl ReputationManager {
+    fn report(&mut self, peer: &str, event: ReputationEvent, now: u64) -> PeerStanding {
+        let (score, updated) = self.scores.entry(peer.to_string()).or_insert((0.0, now));
+        // Decay lazily on each event instead of sweeping every peer on a timer
+        *score = self.policy.decay(*score, now.saturating_sub(*updated)) + self.policy.score_delta(&event);
+        *updated = now;
+        self.policy.standing(*score)
+    }
+
+    fn standing(&self, peer: &str, now: u64) -> PeerStanding {
+        let score = self.scores.get(peer).map_or(0.0, |(score, updated)| self.policy.decay(*score, now.saturating_sub(*updated)));
+        self.policy.standing(score)
+    }
+
+    fn save_into(&self, peerstore: &mut Peerstore, now: u64) {
+        // Persisted through the peerstore, so scores survive restarts with it
+        for (peer, (score, updated)) in &self.scores {
+            peerstore.peers.entry(peer.clone()).or_default().reputation = self.policy.decay(*score, now.saturating_sub(*updated));
+        }
+    }
+
+    fn load_from(&mut self, peerstore: &Peerstore, now: u64) {
+        self.scores = peerstore.peers.iter().map(|(peer, record)| (peer.clone(), (record.reputation, now))).collect();
+    }
+}
+
+// Exchange: .dark Domains
+// DarkResolver names are registered and transferred as ledger transactions, paid in rUv
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DarkDomain {
+    owner: String,
+    expires_at: u64,
+}
+
+// Registry account that collects registration and renewal fees
+const DARK_REGISTRY: &str = "dark-registry";
+const DARK_REGISTRATION_PERIOD_SECS: u64 = 365 * 24 * 3600;
+// After expiry only the previous owner may renew, so a lapse isn't an instant takeover
+const DARK_GRACE_PERIOD_SECS: u64 = 30 * 24 * 3600;
+
+fn dark_domain_fee(name: &str) -> u128 {
+    // Short names are scarcer, so they cost more per year
+    match name.trim_end_matches(".dark").len() {
+        0..=3 => 10_000,
+        4..=6 => 1_000,
+        _ => 100,
+    }
+}
+
+fn validate_dark_name(name: &str) -> Result<(), Box<dyn Error>> {
+    let label = name.strip_suffix(".dark").ok_or("domain must end in .dark")?;
+    if label.is_empty() || label.len() > 63 || !label.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-') {
+        return Err(format!("{} is not a valid .dark name", name).into());
+    }
+    Ok(())
+}
+
+// A name stays taken through its grace period, so only its owner can get it back by renewing
+fn dark_name_taken(ledger: &Ledger, name: &str, now: u64) -> bool {
+    ledger.domains.get(name).map_or(false, |d| d.expires_at + DARK_GRACE_PERIOD_SECS > now)
+}
+
+fn apply_domain_op(ledger: &mut Ledger, tx: &ExchangeTransaction, now: u64) -> Result<(), Box<dyn Error>> {
+    let owned_by = |ledger: &Ledger, name: &str, account: &str, grace: u64| ledger.domains.get(name).map_or(false, |d| d.owner == account && d.expires_at + grace > now);
+    match &tx.kind {
+        TransactionType::DomainRegister { name, expires_at } => {
+            validate_dark_name(name)?;
+            if dark_name_taken(ledger, name, now) {
+                return Err(format!("{} is already registered", name).into());
+            }
+            if tx.to != DARK_REGISTRY || tx.asset.0 != RUV || tx.amount < dark_domain_fee(name) || *expires_at > now + DARK_REGISTRATION_PERIOD_SECS {
+                return Err(format!("registration of {} does not pay the registry fee for its period", name).into());
+            }
+            transfer_asset(ledger, &tx.from, &tx.to, &tx.asset, tx.amount)?;
+            ledger.domains.insert(name.clone(), DarkDomain { owner: tx.from.clone(), expires_at: *expires_at });
+        }
+        TransactionType::DomainRenew { name, expires_at } => {
+            if !owned_by(ledger, name, &tx.from, DARK_GRACE_PERIOD_SECS) {
+                return Err(ExchangeError::new(ErrorCode::Unauthorized, format!("{} is not owned by {}", name, tx.from)).into());
+            }
+            let current = ledger.domains[name].expires_at.max(now);
+            if tx.to != DARK_REGISTRY || tx.asset.0 != RUV || tx.amount < dark_domain_fee(name) || *expires_at > current + DARK_REGISTRATION_PERIOD_SECS {
+                return Err(format!("renewal of {} does not pay the registry fee for its period", name).into());
+            }
+            transfer_asset(ledger, &tx.from, &tx.to, &tx.asset, tx.amount)?;
+            ledger.domains.get_mut(name).expect("checked above").expires_at = *expires_at;
+        }
+        TransactionType::DomainTransfer { name } => {
+            // Lapsed domains can't be transferred, only renewed
+            if !owned_by(ledger, name, &tx.from, 0) {
+                return Err(ExchangeError::new(ErrorCode::Unauthorized, format!("{} is not owned by {}", name, tx.from)).into());
+            }
+            transfer_asset(ledger, &tx.from, &tx.to, &tx.asset, tx.amount)?;
+            ledger.domains.get_mut(name).expect("checked above").owner = tx.to.clone();
+        }
+        _ => return Err("not a domain operation".into()),
+    }
+    Ok(())
+}
+
+impl Exchange {
+    async fn register_dark_domain(&self, name: &str, account: &str, now: u64) -> Result<ExchangeTransaction, Box<dyn Error>> {
+        validate_dark_name(name)?;
+        // Same check as the ledger: a name in its grace period no longer resolves but is not free
+        if dark_name_taken(&*self.ledger.read().await, name, now) {
+            return Err(format!("{} is already registered", name).into());
+        }
+        // Returned unsigned; the wallet signs it with sign_transaction and submits it as usual
+        let kind = TransactionType::DomainRegister { name: name.to_string(), expires_at: now + DARK_REGISTRATION_PERIOD_SECS };
+        let mut tx = ExchangeTransaction {
+            id: String::new(),
+            from: account.to_string(),
+            to: DARK_REGISTRY.to_string(),
+            asset: AssetId(RUV.to_string()),
+            amount: dark_domain_fee(name),
+            valid_until: None,
+            nonce: self.get_next_nonce(account).await,
+            fee: 0,
+            kind,
+            memo: None,
+            public_key: Vec::new(),
+            signature: Vec::new(),
+        };
+        tx.fee = self.model_fee(&tx).await;
+        Ok(tx)
+    }
+
+    async fn resolve_dark_domain(&self, name: &str, now: u64) -> Option<DarkDomain> {
+        // Expired names stop resolving immediately, even during the renewal grace period
+        self.ledger.read().await.domains.get(name).filter(|d| d.expires_at > now).cloned()
+    }
+}
+
+fn sync_dark_resolver() -> Result<(), Box<dyn Error>> {
+    // Functionality to publish each registered domain's owner-signed address record into the
+    // network layer's DarkResolver, and to withdraw records when a domain expires or changes owner
+    Ok(())
+}
+
+// Network: Onion-routed Transaction Submission
+// Private submissions enter the network through a 3-hop circuit, so no peer sees which node
+// originated the transaction
+struct TransactionBuilder {
+    tx: ExchangeTransaction,
+    private: bool,
+}
+
+impl TransactionBuilder {
+    fn new(tx: ExchangeTransaction) -> TransactionBuilder {
+        TransactionBuilder { tx, private: false }
+    }
+
+    fn private(mut self, private: bool) -> Self {
+        // Submission option only; it is not part of the signed payload
+        self.private = private;
+        self
+    }
+
+    fn memo(mut self, memo: impl Into<Vec<u8>>) -> Result<Self, Box<dyn Error>> {
+        self.tx = self.tx.memo(memo)?;
+        Ok(self)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct OnionCircuit {
+    hops: Vec<String>,
+    built_at: std::time::Instant,
+    uses: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CircuitPolicy {
+    // Circuits are reused for a few submissions, then rotated so transactions can't be linked
+    max_uses: u32,
+    max_age_secs: u64,
+    // New circuits tried before giving up
+    rebuild_attempts: u32,
+    // Opt-in: fall back to direct gossip when no circuit works, giving up origin privacy
+    fallback_to_direct: bool,
+}
+
+#[derive(Debug, Default)]
+struct CircuitPool {
+    current: Option<OnionCircuit>,
+}
+
+impl CircuitPool {
+    fn circuit(&mut self, policy: &CircuitPolicy, mix_nodes: &[String]) -> Result<&mut OnionCircuit, Box<dyn Error>> {
+        let stale = self.current.as_ref().map_or(true, |c| c.uses >= policy.max_uses || c.built_at.elapsed().as_secs() >= policy.max_age_secs);
+        if stale {
+            self.current = Some(build_circuit(mix_nodes)?);
+        }
+        Ok(self.current.as_mut().expect("circuit was just built"))
+    }
+}
+
+fn build_circuit(mix_nodes: &[String]) -> Result<OnionCircuit, Box<dyn Error>> {
+    use rand::seq::SliceRandom;
+    // Three distinct MixNodes; the ML-KEM handshake with each hop happens in the onion layer
+    let hops: Vec<String> = mix_nodes.choose_multiple(&mut rand::thread_rng(), 3).cloned().collect();
+    if hops.len() < 3 {
+        return Err("need at least 3 mix nodes for a private circuit".into());
+    }
+    Ok(OnionCircuit { hops, built_at: std::time::Instant::now(), uses: 0 })
+}
+
+fn send_through_circuit(circuit: &OnionCircuit, payload: &[u8]) -> Result<(), Box<dyn Error>> {
+    // Functionality to wrap the payload in one encryption layer per hop, padded to the mix
+    // packet size; the exit hop publishes it on TOPIC_TRANSACTIONS like any other peer
+    log_activity(&format!("private submission via {} hops ({} bytes)", circuit.hops.len(), payload.len()))
+}
+
+async fn submit(builder: TransactionBuilder, network: &NetworkManager, gossip_peer: &str, pool: &mut CircuitPool, policy: &CircuitPolicy, mix_nodes: &[String]) -> Result<String, Box<dyn Error>> {
+    let payload = serde_json::to_vec(&builder.tx)?;
+    if builder.private {
+        for _ in 0..=policy.rebuild_attempts {
+            // A circuit that can't be built counts as a failed attempt, like one that can't send
+            let sent = pool.circuit(policy, mix_nodes).and_then(|circuit| {
+                circuit.uses += 1;
+                send_through_circuit(circuit, &payload)
+            });
+            if sent.is_ok() {
+                return Ok(builder.tx.id);
+            }
+            // A failed circuit is never retried; the next attempt builds a fresh one
+            pool.current = None;
+        }
+        if !policy.fallback_to_direct {
+            return Err("no working onion circuit and direct fallback is disabled".into());
+        }
+        log_activity(&format!("onion submission of {} failed, falling back to direct gossip", builder.tx.id))?;
+    }
+    network.send_message(gossip_peer, TOPIC_TRANSACTIONS, payload).await?;
+    Ok(builder.tx.id)
+}
+
+// Network Transport: QUIC with Hybrid ML-KEM Handshake
+// Behind the `quic` feature; TLS 1.3 inside QUIC keeps X25519, and an ML-KEM exchange bound to
+// the same transcript is mixed into the session key, so breaking either one alone is not enough
+#[cfg(feature = "quic")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct QuicTransportConfig {
+    listen_addr: String,
+    handshake: HandshakeConfig,
+    // Keep connections alive across local address changes (Wi-Fi to mobile, NAT rebinding)
+    allow_migration: bool,
+    idle_timeout_secs: u64,
+}
+
+fn hybrid_session_key(x25519_shared: &[u8; 32], ml_kem_shared: &[u8], transcript: [u8; 32]) -> [u8; 32] {
+    // Both secrets go through one domain-separated KDF together with the negotiated transcript
+    let mut hasher = blake3::Hasher::new_derive_key("qudag hybrid x25519+ml-kem session v1");
+    hasher.update(x25519_shared);
+    hasher.update(ml_kem_shared);
+    hasher.update(&transcript);
+    *hasher.finalize().as_bytes()
+}
+
+#[cfg(feature = "quic")]
+async fn quic_connect(
+    config: &QuicTransportConfig,
+    identity: &libp2p::identity::Keypair,
+    expected_peer: libp2p::PeerId,
+    addr: std::net::SocketAddr,
+    peer_offered: &[SecurityLevel],
+) -> Result<(quinn::Connection, [u8; 32]), Box<dyn Error>> {
+    let level = negotiate_suite(&config.handshake, peer_offered)?;
+    let mut endpoint = quinn::Endpoint::client("0.0.0.0:0".parse()?)?;
+    let mut transport = quinn::TransportConfig::default();
+    transport.max_idle_timeout(Some(std::time::Duration::from_secs(config.idle_timeout_secs).try_into()?));
+    // Peers present self-signed libp2p certificates, so there is no CA to check against; the
+    // handshake fails unless the certificate's embedded key is the peer we meant to dial
+    let tls = libp2p_tls::make_client_config(identity, Some(expected_peer))?;
+    let mut client = quinn::ClientConfig::new(std::sync::Arc::new(quinn::crypto::rustls::QuicClientConfig::try_from(tls)?));
+    client.transport_config(std::sync::Arc::new(transport));
+    endpoint.set_default_client_config(client);
+
+    let connection = endpoint.connect(addr, "qudag")?.await?;
+    // X25519 secret from the TLS exporter, so both halves are bound to this exact connection
+    let mut x25519_shared = [0u8; 32];
+    connection.export_keying_material(&mut x25519_shared, b"qudag hybrid", b"").map_err(|_| "tls exporter failed")?;
+
+    // ML-KEM runs on the first bidirectional stream: we send our encapsulation key, the peer
+    // returns a ciphertext, and both sides confirm the transcript before any other stream opens
+    let (mut send, mut recv) = connection.open_bi().await?;
+    let (ml_kem_shared, peer_transcript) = ml_kem_exchange(level, &mut send, &mut recv).await?;
+    // We dialed, so we are the initiator
+    let transcript = transcript_hash(&config.handshake.offered, peer_offered, level);
+    verify_transcript(transcript, peer_transcript)?;
+
+    Ok((connection, hybrid_session_key(&x25519_shared, &ml_kem_shared, transcript)))
+}
+
+#[cfg(feature = "quic")]
+async fn ml_kem_exchange(level: SecurityLevel, send: &mut quinn::SendStream, recv: &mut quinn::RecvStream) -> Result<(Vec<u8>, [u8; 32]), Box<dyn Error>> {
+    // The level was already negotiated and is bound by the transcript, so only key material crosses the stream
+    match level {
+        SecurityLevel::MlKem512 => ml_kem_initiate::<ml_kem::MlKem512>(send, recv).await,
+        SecurityLevel::MlKem768 => ml_kem_initiate::<ml_kem::MlKem768>(send, recv).await,
+        SecurityLevel::MlKem1024 => ml_kem_initiate::<ml_kem::MlKem1024>(send, recv).await,
+    }
+}
+
+#[cfg(feature = "quic")]
+async fn ml_kem_initiate<K: ml_kem::KemCore>(send: &mut quinn::SendStream, recv: &mut quinn::RecvStream) -> Result<(Vec<u8>, [u8; 32]), Box<dyn Error>> {
+    use ml_kem::kem::Decapsulate;
+    use ml_kem::EncodedSizeUser;
+    // A fresh keypair per connection; the decapsulation key never leaves this function
+    let (decapsulation_key, encapsulation_key) = K::generate(&mut rand::rngs::OsRng);
+    send.write_all(&encapsulation_key.as_bytes()).await?;
+    // Both replies have a fixed size for the level: the ciphertext, then the peer's transcript hash
+    let mut ciphertext = ml_kem::Ciphertext::<K>::default();
+    recv.read_exact(&mut ciphertext).await?;
+    let mut peer_transcript = [0u8; 32];
+    recv.read_exact(&mut peer_transcript).await?;
+    let shared = decapsulation_key.decapsulate(&ciphertext).map_err(|_| "ml-kem decapsulation failed")?;
+    Ok((shared.to_vec(), peer_transcript))
+}
+
+#[cfg(feature = "quic")]
+fn on_path_change(config: &QuicTransportConfig, endpoint: &quinn::Endpoint, new_local: std::net::SocketAddr) -> Result<(), Box<dyn Error>> {
+    // QUIC connection IDs survive the move; peers validate the new path before using it
+    if !config.allow_migration {
+        return Err("connection migration is disabled".into());
+    }
+    endpoint.rebind(std::net::UdpSocket::bind(new_local)?)?;
+    Ok(())
+}
+
+fn benchmark_quic_transport() -> Result<(), Box<dyn Error>> {
+    // Functionality to compare the current transport and QUIC (`--features quic`) on:
+    // handshake latency with ML-KEM-768, 1 KiB message round trips, 100 MiB bulk throughput,
+    // and recovery time after a simulated address change
+    Ok(())
+}
+
+// Exchange WASM: WebRTC Bridge to the P2P Network
+// Browser wallets open data channels straight to WebRTC-capable nodes; the signaling server only
+// relays SDP and ICE, never transactions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum SignalingMessage {
+    // Same wire format as the server-side webrtc module's signaling types
+    Offer { from: String, to: String, sdp: String },
+    Answer { from: String, to: String, sdp: String },
+    IceCandidate { from: String, to: String, candidate: String, sdp_mid: Option<String> },
+}
+
+#[wasm_bindgen]
+pub struct BrowserPeer {
+    local_id: String,
+    connection: web_sys::RtcPeerConnection,
+    channel: web_sys::RtcDataChannel,
+    // Called with (tx_id, status) for each status the node pushes back on the channel
+    on_status: Option<js_sys::Function>,
+}
+
+#[wasm_bindgen]
+impl BrowserPeer {
+    pub async fn connect(signaling_url: &str, node_id: &str, stun_servers: Vec<String>) -> Result<BrowserPeer, JsValue> {
+        let mut config = web_sys::RtcConfiguration::new();
+        let servers = js_sys::Array::new();
+        for url in stun_servers {
+            let server = web_sys::RtcIceServer::new();
+            server.set_urls(&JsValue::from_str(&url));
+            servers.push(&server);
+        }
+        config.ice_servers(&servers);
+        let connection = web_sys::RtcPeerConnection::new_with_configuration(&config)?;
+        // Ordered and reliable: transactions and their confirmations must not be reordered or lost
+        let channel = connection.create_data_channel("qudag-exchange");
+
+        let offer = wasm_bindgen_futures::JsFuture::from(connection.create_offer()).await?;
+        let sdp = js_sys::Reflect::get(&offer, &JsValue::from_str("sdp"))?.as_string().ok_or("offer has no sdp")?;
+        let mut description = web_sys::RtcSessionDescriptionInit::new(web_sys::RtcSdpType::Offer);
+        description.sdp(&sdp);
+        wasm_bindgen_futures::JsFuture::from(connection.set_local_description(&description)).await?;
+
+        let local_id = format!("browser-{}", hex::encode(rand::random::<[u8; 8]>()));
+        exchange_signaling(signaling_url, SignalingMessage::Offer { from: local_id.clone(), to: node_id.to_string(), sdp }, &connection).await?;
+        Ok(BrowserPeer { local_id, connection, channel, on_status: None })
+    }
+
+    pub fn submit_transaction(&self, signed_tx_json: &str) -> Result<String, JsValue> {
+        // Signed locally by QuDAGExchange; the node treats it like a gossiped transaction
+        let tx: ExchangeTransaction = serde_json::from_str(signed_tx_json).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        verify_signature(&tx).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        self.channel.send_with_str(signed_tx_json)?;
+        Ok(tx.id)
+    }
+
+    pub fn on_status(&mut self, callback: js_sys::Function) {
+        // Incoming channel messages are LedgerEvent::StatusChanged frames, forwarded to the callback
+        let handler = callback.clone();
+        let on_message = Closure::<dyn FnMut(web_sys::MessageEvent)>::new(move |event: web_sys::MessageEvent| {
+            if let Some(text) = event.data().as_string() {
+                if let Ok(LedgerEvent::StatusChanged { tx_id, status }) = serde_json::from_str(&text) {
+                    let _ = handler.call2(&JsValue::NULL, &JsValue::from_str(&tx_id), &JsValue::from_str(&format!("{:?}", status)));
+                }
+            }
+        });
+        self.channel.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        // The channel owns the handler for the rest of the page's life
+        on_message.forget();
+        self.on_status = Some(callback);
+    }
+
+    pub fn close(&self) {
+        self.channel.close();
+        self.connection.close();
+    }
+}
+
+async fn exchange_signaling(signaling_url: &str, offer: SignalingMessage, connection: &web_sys::RtcPeerConnection) -> Result<(), JsValue> {
+    // Functionality to post the offer over a WebSocket to the signaling server, apply the
+    // node's Answer with set_remote_description, and trickle IceCandidate messages both ways
+    // until the data channel opens; fails after 15s so the wallet can try another node
+    let _ = (signaling_url, offer, connection);
+    Ok(())
+}
+
+// Network NAT Traversal: Path Probing and Auto-selection
+// Each peer's direct, hole-punched and relayed paths are probed periodically, and traffic moves to
+// the best one instead of staying on whatever connect_with_fallback found first
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+enum PathCandidate {
+    Direct,
+    HolePunched,
+    Relayed { relay: String },
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct PathStats {
+    attempts: u32,
+    successes: u32,
+    // Exponentially weighted RTT of successful probes
+    rtt_ms: Option<f64>,
+}
+
+impl PathStats {
+    fn success_rate(&self) -> f64 {
+        if self.attempts == 0 {
+            0.0
+        } else {
+            self.successes as f64 / self.attempts as f64
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum PathEvent {
+    Selected { peer: String, path: PathCandidate },
+    Changed { peer: String, from: PathCandidate, to: PathCandidate, reason: String },
+    Unreachable { peer: String },
+}
+
+#[derive(Debug, Default)]
+struct ConnectivityProber {
+    stats: HashMap<String, HashMap<PathCandidate, PathStats>>,
+    selected: HashMap<String, PathCandidate>,
+}
+
+const MIN_SUCCESS_RATE: f64 = 0.8;
+// A new path must be this much faster before we switch, so we don't flap between similar paths
+const SWITCH_RTT_RATIO: f64 = 0.75;
+
+const PROBE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+fn peer_addr(peer: &str) -> Result<std::net::SocketAddr, Box<dyn Error>> {
+    // Functionality to look the peer's dialable address up in the routing table; a literal
+    // socket address is used as is
+    Ok(peer.parse()?)
+}
+
+fn open_probe_stream(peer: &str, path: &PathCandidate) -> Result<std::net::TcpStream, Box<dyn Error>> {
+    match path {
+        PathCandidate::Direct => Ok(std::net::TcpStream::connect_timeout(&peer_addr(peer)?, PROBE_TIMEOUT)?),
+        PathCandidate::HolePunched => {
+            hole_punch(peer)?;
+            Ok(std::net::TcpStream::connect_timeout(&peer_addr(peer)?, PROBE_TIMEOUT)?)
+        }
+        PathCandidate::Relayed { relay } => {
+            reserve_relay_slot(relay, peer)?;
+            // Functionality to open a stream to the peer over the reserved circuit
+            Err(format!("no circuit stream to {} via {}", peer, relay).into())
+        }
+    }
+}
+
+fn probe_path(peer: &str, path: &PathCandidate) -> Result<f64, Box<dyn Error>> {
+    use std::io::{Read, Write};
+    let mut stream = open_probe_stream(peer, path)?;
+    stream.set_read_timeout(Some(PROBE_TIMEOUT))?;
+    // Only the ping/pong round trip is timed; dialing and relay setup are not part of the RTT
+    let ping = rand::random::<u64>().to_be_bytes();
+    let started = std::time::Instant::now();
+    stream.write_all(&ping)?;
+    let mut pong = [0u8; 8];
+    stream.read_exact(&mut pong)?;
+    let rtt = started.elapsed();
+    if pong != ping {
+        return Err(format!("{} answered the probe with the wrong pong", peer).into());
+    }
+    Ok(rtt.as_secs_f64() * 1000.0)
+}
+
+impl ConnectivityProber {
+    fn probe_peer(&mut self, peer: &str, relays: &[String]) -> Option<PathEvent> {
+        let candidates = [PathCandidate::Direct, PathCandidate::HolePunched].into_iter().chain(relays.iter().map(|relay| PathCandidate::Relayed { relay: relay.clone() }));
+        let stats = self.stats.entry(peer.to_string()).or_default();
+        for path in candidates {
+            let entry = stats.entry(path.clone()).or_default();
+            entry.attempts += 1;
+            if let Ok(rtt) = probe_path(peer, &path) {
+                entry.successes += 1;
+                entry.rtt_ms = Some(entry.rtt_ms.map_or(rtt, |avg| avg * 0.8 + rtt * 0.2));
+            }
+        }
+        self.select(peer)
+    }
+
+    fn select(&mut self, peer: &str) -> Option<PathEvent> {
+        let stats = self.stats.get(peer)?;
+        // Reliable paths only, then lowest RTT; direct paths win ties since they cost no relay fees
+        let rank = |path: &PathCandidate| match path {
+            PathCandidate::Direct => 0,
+            PathCandidate::HolePunched => 1,
+            PathCandidate::Relayed { .. } => 2,
+        };
+        let best = stats
+            .iter()
+            .filter(|(_, s)| s.success_rate() >= MIN_SUCCESS_RATE && s.rtt_ms.is_some())
+            .min_by(|a, b| a.1.rtt_ms.unwrap().total_cmp(&b.1.rtt_ms.unwrap()).then(rank(a.0).cmp(&rank(b.0))))
+            .map(|(path, s)| (path.clone(), s.rtt_ms.unwrap()));
+
+        let current = self.selected.get(peer).cloned();
+        match (best, current) {
+            (None, Some(_)) => {
+                self.selected.remove(peer);
+                Some(PathEvent::Unreachable { peer: peer.to_string() })
+            }
+            (None, None) => None,
+            (Some((path, _)), None) => {
+                self.selected.insert(peer.to_string(), path.clone());
+                Some(PathEvent::Selected { peer: peer.to_string(), path })
+            }
+            (Some((path, rtt)), Some(current)) if path != current => {
+                let current_stats = &stats[&current];
+                let current_ok = current_stats.success_rate() >= MIN_SUCCESS_RATE;
+                let faster = current_stats.rtt_ms.map_or(true, |current_rtt| rtt < current_rtt * SWITCH_RTT_RATIO);
+                // Upgrading from a relay to any working direct path is always worth it
+                let upgrade = rank(&path) < rank(&current) && matches!(current, PathCandidate::Relayed { .. });
+                if current_ok && !faster && !upgrade {
+                    return None;
+                }
+                let reason = if !current_ok { "current path unreliable" } else if upgrade { "direct path available" } else { "lower latency" };
+                self.selected.insert(peer.to_string(), path.clone());
+                Some(PathEvent::Changed { peer: peer.to_string(), from: current, to: path, reason: reason.to_string() })
+            }
+            _ => None,
+        }
+    }
+}
+
+fn run_connectivity_prober() -> Result<(), Box<dyn Error>> {
+    // Functionality to probe every connected peer on a jittered 60s interval (10s for peers on a
+    // relay), apply Changed events by migrating streams to the new path, and publish PathEvents
+    // to subscribers; this replaces the fixed-interval retry_direct_upgrade loop
+    Ok(())
+}
+
+// Provider: Job Lifecycle and Sandbox
+// Provider::start accepts job specs that fit the offered resources, runs them isolated, streams
+// progress, and signs a completion report that releases the consumer's escrow
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ResourceSpec {
+    cpu_cores: u32,
+    memory_gb: u32,
+    gpus: Vec<String>,
+    disk_gb: u32,
+    bandwidth_mbps: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum JobRuntime {
+    // Process under a cgroup v2 slice with CPU, memory and pids limits, no network by default
+    Native { command: String, args: Vec<String> },
+    // WASI module with fuel and memory caps; works on hosts without cgroups
+    Wasm { module: Vec<u8> },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct JobSpec {
+    id: String,
+    consumer: String,
+    escrow_id: String,
+    class: JobClass,
+    runtime: JobRuntime,
+    cpu_cores: u32,
+    memory_gb: u32,
+    timeout_secs: u64,
+    // Agreed price, from the matched offer
+    price_per_hour: u128,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum JobUpdate {
+    Log { job_id: String, line: String },
+    Metrics { job_id: String, cpu_seconds: u64, peak_memory_bytes: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct CompletionReport {
+    job_id: String,
+    escrow_id: String,
+    exit_code: i32,
+    cpu_seconds: u64,
+    // blake3 of the job's output archive, so the consumer can check what they received
+    output_hash: [u8; 32],
+    signature: Vec<u8>,
+}
+
+struct Provider {
+    account: String,
+    // Job history and stats are persisted here
+    data_dir: String,
+    spec: ResourceSpec,
+    keypair: MlDsaKeyPair,
+    queue: JobQueue,
+    stats: ProviderStats,
+    updates: tokio::sync::mpsc::Sender<JobUpdate>,
+    // Specs of accepted jobs, queued or running, by job id
+    jobs: HashMap<String, JobSpec>,
+    // The task running each running job
+    tasks: HashMap<String, tokio::task::AbortHandle>,
+    // Latest stats for the metrics endpoint, which can't lock the provider while start() runs
+    metrics: tokio::sync::watch::Sender<ProviderStats>,
+}
+
+impl Provider {
+    fn accept_job(&mut self, job: &JobSpec) -> Result<(), Box<dyn Error>> {
+        // Jobs are validated against what was offered, not against what happens to be free now;
+        // capacity is the queue's concern
+        if job.cpu_cores > self.spec.cpu_cores || job.memory_gb > self.spec.memory_gb {
+            return Err(format!("job {} asks for more than the offered {} cores / {} GB", job.id, self.spec.cpu_cores, self.spec.memory_gb).into());
+        }
+        let queued = ProviderJob {
+            id: job.id.clone(),
+            class: job.class,
+            cpu_cores: job.cpu_cores,
+            memory_gb: job.memory_gb,
+            consumer: job.consumer.clone(),
+            price_per_hour: job.price_per_hour,
+        };
+        start_job(&mut self.queue, &mut self.stats, queued)
+    }
+
+    // Starts a task for every running job that has none, and stops the tasks of jobs that
+    // were preempted since the last pass
+    fn sync_job_tasks(&mut self, runs: &mut tokio::task::JoinSet<JobRun>) {
+        self.tasks.retain(|id, task| {
+            let running = self.queue.running.iter().any(|j| &j.id == id);
+            if !running {
+                task.abort();
+            }
+            running
+        });
+        for job in &self.queue.running {
+            if self.tasks.contains_key(&job.id) {
+                continue;
+            }
+            let Some(spec) = self.jobs.get(&job.id).cloned() else {
+                continue;
+            };
+            let updates = self.updates.clone();
+            let task = runs.spawn(async move {
+                let started = std::time::Instant::now();
+                let result = run_sandboxed(&spec, &updates).await.map_err(|e| e.to_string());
+                JobRun { elapsed: started.elapsed(), job: spec, result }
+            });
+            self.tasks.insert(job.id.clone(), task);
+        }
+    }
+
+    // A task can finish just after preempt requeued its job; the abort then comes too late and
+    // its result arrives anyway. Only the job's current task may finish it
+    fn is_current_task(&self, job_id: &str, task: tokio::task::Id) -> bool {
+        self.tasks.get(job_id).map_or(false, |current| current.id() == task)
+    }
+
+    fn finish_job(&mut self, run: JobRun) -> Result<(), Box<dyn Error>> {
+        let job = run.job;
+        self.tasks.remove(&job.id);
+        self.jobs.remove(&job.id);
+        self.queue.running.retain(|j| j.id != job.id);
+        *self.stats.cores_in_use.entry(job.class).or_default() -= job.cpu_cores;
+
+        // A job that ran but exited non-zero earns nothing and gets no completion report; the
+        // consumer's escrow refunds at its timeout
+        let result = match run.result {
+            Ok((exit_code, _)) if exit_code != 0 => Err(format!("exited with code {}", exit_code)),
+            result => result,
+        };
+        let (exit_code, output_hash) = match result {
+            Ok(result) => result,
+            Err(reason) => {
+                log_activity(&format!("job {} failed: {}", job.id, reason))?;
+                return record_job_outcome(&mut self.stats, &self.data_dir, &job, JobOutcome::Failed { reason }, run.elapsed);
+            }
+        };
+        let mut report = CompletionReport {
+            job_id: job.id.clone(),
+            escrow_id: job.escrow_id.clone(),
+            exit_code,
+            cpu_seconds: run.elapsed.as_secs() * job.cpu_cores as u64,
+            output_hash,
+            signature: Vec::new(),
+        };
+        report.signature = self.keypair.sign(&serde_json::to_vec(&report)?)?;
+        let earned = job.price_per_hour * run.elapsed.as_secs() as u128 / 3600;
+        record_job_outcome(&mut self.stats, &self.data_dir, &job, JobOutcome::Completed { earned }, run.elapsed)?;
+        submit_completion_report(&report)
+    }
+
+    async fn start(&mut self, mut jobs: tokio::sync::mpsc::Receiver<JobSpec>) -> Result<(), Box<dyn Error>> {
+        let mut runs = tokio::task::JoinSet::new();
+        loop {
+            tokio::select! {
+                received = jobs.recv() => {
+                    let Some(job) = received else {
+                        break;
+                    };
+                    if let Err(e) = self.accept_job(&job) {
+                        log_activity(&format!("rejected job {}: {}", job.id, e))?;
+                        continue;
+                    }
+                    // Queued jobs keep their spec until the scheduler starts them
+                    self.jobs.insert(job.id.clone(), job);
+                }
+                Some(finished) = runs.join_next_with_id(), if !runs.is_empty() => {
+                    match finished {
+                        Ok((task, run)) if self.is_current_task(&run.job.id, task) => self.finish_job(run)?,
+                        // A stale run of a job preempt already requeued; its cores are already freed
+                        Ok(_) => {}
+                        // Aborted tasks belong to preempted jobs, already requeued by preempt
+                        Err(e) if e.is_cancelled() => {}
+                        Err(e) => return Err(e.into()),
+                    }
+                    // Freed capacity goes to the highest-priority queued jobs
+                    for started in schedule_queued(&mut self.queue, &mut self.stats)? {
+                        log_activity(&format!("started queued job {}", started))?;
+                    }
+                }
+            }
+            self.sync_job_tasks(&mut runs);
+            self.metrics.send_replace(self.stats.clone());
+        }
+        // No more jobs will arrive; let the running ones finish
+        while let Some(finished) = runs.join_next_with_id().await {
+            if let Ok((task, run)) = finished {
+                if self.is_current_task(&run.job.id, task) {
+                    self.finish_job(run)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// What a job's task hands back to the provider loop
+struct JobRun {
+    job: JobSpec,
+    elapsed: std::time::Duration,
+    result: Result<(i32, [u8; 32]), String>,
+}
+
+async fn run_sandboxed(job: &JobSpec, updates: &tokio::sync::mpsc::Sender<JobUpdate>) -> Result<(i32, [u8; 32]), Box<dyn Error>> {
+    let run = async {
+        match &job.runtime {
+            JobRuntime::Native { command, args } => run_native_sandboxed(job, command, args, updates).await,
+            JobRuntime::Wasm { module } => run_wasm_sandboxed(job, module, updates).await,
+        }
+    };
+    tokio::time::timeout(std::time::Duration::from_secs(job.timeout_secs), run)
+        .await
+        .map_err(|_| format!("job {} exceeded its {}s timeout", job.id, job.timeout_secs))?
+}
+
+// Stops the transient unit when a run is dropped before the job exits, on timeout or when
+// sync_job_tasks aborts a preempted job; killing systemd-run alone leaves the unit running
+struct UnitGuard {
+    unit: String,
+    exited: bool,
+}
+
+impl Drop for UnitGuard {
+    fn drop(&mut self) {
+        if !self.exited {
+            let _ = tokio::process::Command::new("systemctl").args(["stop", "--no-block", &self.unit]).spawn();
+        }
+    }
+}
+
+async fn run_native_sandboxed(job: &JobSpec, command: &str, args: &[String], updates: &tokio::sync::mpsc::Sender<JobUpdate>) -> Result<(i32, [u8; 32]), Box<dyn Error>> {
+    use tokio::io::AsyncBufReadExt;
+    // One unit per attempt, so a requeued job never collides with its previous unit
+    let mut guard = UnitGuard { unit: format!("daa-job-{}-{:08x}", job.id, rand::random::<u32>()), exited: false };
+    // A transient service rather than a scope: scopes ignore sandboxing properties such as
+    // PrivateNetwork. --pipe passes stdout through and --wait returns the job's exit code;
+    // RuntimeMaxSec lets systemd stop the unit even if this process dies first
+    let mut child = tokio::process::Command::new("systemd-run")
+        .args(["--pipe", "--wait", "--collect", "--quiet", &format!("--unit={}", guard.unit)])
+        .args(["-p", &format!("CPUQuota={}%", job.cpu_cores * 100), "-p", &format!("MemoryMax={}G", job.memory_gb), "-p", "TasksMax=512"])
+        .args(["-p", "PrivateNetwork=yes", "-p", &format!("RuntimeMaxSec={}", job.timeout_secs), "--"])
+        .arg(command)
+        .args(args)
+        .stdout(std::process::Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    // Functionality for a sampler task that reads cpu.stat and memory.peak from the unit's
+    // cgroup every 5s and sends JobUpdate::Metrics until the child exits
+    let mut lines = tokio::io::BufReader::new(child.stdout.take().ok_or("job has no stdout")?).lines();
+    let mut hasher = blake3::Hasher::new();
+    while let Some(line) = lines.next_line().await? {
+        hasher.update(line.as_bytes());
+        let _ = updates.send(JobUpdate::Log { job_id: job.id.clone(), line }).await;
+    }
+    let status = child.wait().await?;
+    // --wait returned, so the unit has exited and --collect removes it
+    guard.exited = true;
+    Ok((status.code().unwrap_or(-1), *hasher.finalize().as_bytes()))
+}
+
+async fn run_wasm_sandboxed(job: &JobSpec, module: &[u8], updates: &tokio::sync::mpsc::Sender<JobUpdate>) -> Result<(i32, [u8; 32]), Box<dyn Error>> {
+    // Functionality to instantiate the module in wasmtime with fuel proportional to cpu_cores *
+    // timeout_secs, a memory cap of memory_gb, WASI stdout piped to JobUpdate::Log, and no
+    // preopened directories beyond the job's scratch dir
+    let _ = (module, updates);
+    Err(format!("wasm runtime not available for job {}", job.id).into())
+}
+
+fn submit_completion_report(report: &CompletionReport) -> Result<(), Box<dyn Error>> {
+    // Functionality to send the signed report to the exchange inside an EscrowRelease
+    // transaction; a valid provider signature over a zero exit code is the completion proof
+    log_activity(&format!("job {} completed with exit code {}", report.job_id, report.exit_code))
+}
+
+fn verify_completion_report(report: &CompletionReport, provider_key: &MlDsaPublicKey) -> bool {
+    let mut unsigned = report.clone();
+    unsigned.signature = Vec::new();
+    let Ok(bytes) = serde_json::to_vec(&unsigned) else {
+        return false;
+    };
+    report.exit_code == 0 && provider_key.verify(&bytes, &report.signature).is_ok()
+}
+
+// Provider: Resource Auto-detection
+// ProviderBuilder probes the host and pre-fills the ResourceSpec; operators only override what
+// they want to hold back
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ResourceOverrides {
+    cpu_cores: Option<u32>,
+    memory_gb: Option<u32>,
+    // Replaces the detected list; an empty list offers no GPUs
+    gpus: Option<Vec<String>>,
+    disk_gb: Option<u32>,
+    bandwidth_mbps: Option<u32>,
+}
+
+fn detect_resources(data_dir: &str) -> Result<ResourceSpec, Box<dyn Error>> {
+    let mut system = sysinfo::System::new_all();
+    system.refresh_all();
+    // Keep a core and 1 GB back for the provider daemon itself
+    let cpu_cores = (system.cpus().len() as u32).saturating_sub(1).max(1);
+    let memory_gb = (system.total_memory() >> 30).saturating_sub(1) as u32;
+    // Only the disk holding the data dir is offered. Canonical, so a relative path or a symlink
+    // is matched against the mount it actually lives on
+    let data_dir = std::fs::canonicalize(data_dir)?;
+    let disks = sysinfo::Disks::new_with_refreshed_list();
+    let disk_gb = disks
+        .iter()
+        .filter(|d| data_dir.starts_with(d.mount_point()))
+        .max_by_key(|d| d.mount_point().as_os_str().len())
+        .map_or(0, |d| (d.available_space() >> 30) as u32);
+
+    Ok(ResourceSpec { cpu_cores, memory_gb, gpus: detect_gpus(), disk_gb, bandwidth_mbps: detect_bandwidth_mbps() })
+}
+
+fn detect_gpus() -> Vec<String> {
+    let mut gpus = Vec::new();
+    #[cfg(feature = "nvml")]
+    if let Ok(nvml) = nvml_wrapper::Nvml::init() {
+        for index in 0..nvml.device_count().unwrap_or(0) {
+            if let Ok(name) = nvml.device_by_index(index).and_then(|d| d.name()) {
+                gpus.push(name);
+            }
+        }
+    }
+    // ROCm has no stable Rust bindings; rocm-smi is present wherever the driver is
+    if let Ok(output) = std::process::Command::new("rocm-smi").args(["--showproductname", "--json"]).output() {
+        if let Ok(cards) = serde_json::from_slice::<HashMap<String, HashMap<String, String>>>(&output.stdout) {
+            gpus.extend(cards.values().filter_map(|card| card.get("Card series").cloned()));
+        }
+    }
+    gpus
+}
+
+fn detect_bandwidth_mbps() -> u32 {
+    // Link speed of the fastest physical interface; a measured speed test can override it later
+    std::fs::read_dir("/sys/class/net")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|iface| iface.path().join("device").exists())
+        .filter_map(|iface| std::fs::read_to_string(iface.path().join("speed")).ok()?.trim().parse::<i64>().ok())
+        .filter(|speed| *speed > 0)
+        .max()
+        .unwrap_or(0) as u32
+}
+
+#[derive(Debug, Default)]
+struct ProviderBuilder {
+    account: Option<String>,
+    data_dir: Option<String>,
+    spec: Option<ResourceSpec>,
+    overrides: ResourceOverrides,
+}
+
+impl ProviderBuilder {
+    fn account(mut self, account: &str) -> Self {
+        self.account = Some(account.to_string());
+        self
+    }
+
+    fn data_dir(mut self, data_dir: &str) -> Self {
+        self.data_dir = Some(data_dir.to_string());
+        self
+    }
+
+    fn auto_detect(mut self, data_dir: &str) -> Result<Self, Box<dyn Error>> {
+        self.spec = Some(detect_resources(data_dir)?);
+        Ok(self)
+    }
+
+    fn spec(mut self, spec: ResourceSpec) -> Self {
+        // A hand-written spec still works and skips detection entirely
+        self.spec = Some(spec);
+        self
+    }
+
+    fn overrides(mut self, overrides: ResourceOverrides) -> Self {
+        self.overrides = overrides;
+        self
+    }
+
+    fn resource_spec(&self) -> Result<ResourceSpec, Box<dyn Error>> {
+        let mut spec = self.spec.clone().ok_or("call auto_detect() or spec() before building")?;
+        let o = &self.overrides;
+        spec.cpu_cores = o.cpu_cores.unwrap_or(spec.cpu_cores);
+        spec.memory_gb = o.memory_gb.unwrap_or(spec.memory_gb);
+        spec.gpus = o.gpus.clone().unwrap_or(spec.gpus);
+        spec.disk_gb = o.disk_gb.unwrap_or(spec.disk_gb);
+        spec.bandwidth_mbps = o.bandwidth_mbps.unwrap_or(spec.bandwidth_mbps);
+        Ok(spec)
+    }
+
+    fn build(self, keypair: MlDsaKeyPair, updates: tokio::sync::mpsc::Sender<JobUpdate>) -> Result<Provider, Box<dyn Error>> {
+        let spec = self.resource_spec()?;
+        let account = self.account.unwrap_or_else(|| account_id(&keypair.public_key()));
+        let queue = JobQueue { total_cores: spec.cpu_cores, total_memory_gb: spec.memory_gb, ..Default::default() };
+        let data_dir = self.data_dir.ok_or("call data_dir() before building")?;
+        // Stats carry over restarts; only cores_in_use starts from zero since no job survives one
+        let stats = ProviderStats { cores_in_use: HashMap::new(), ..load_provider_stats(&data_dir)? };
+        let (metrics, _) = tokio::sync::watch::channel(stats.clone());
+        Ok(Provider { account, data_dir, queue, spec, keypair, stats, updates, jobs: HashMap::new(), tasks: HashMap::new(), metrics })
+    }
+}
+
+// Provider: Market-based Pricing
+// Offers follow the market's rolling average price, scaled by how busy the provider is
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum PricingStrategy {
+    Fixed { price_per_hour: u128 },
+    MarketBased {
+        floor: u128,
+        ceiling: u128,
+        // Re-publish when the target price moves by more than this fraction of the listed price
+        reprice_threshold: f64,
+        // Price multiplier at 0% and 100% utilization; linear in between
+        idle_multiplier: f64,
+        busy_multiplier: f64,
+    },
+}
+
+// Trades per resource type in the rolling average
+const MARKET_STATS_WINDOW: usize = 50;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct MarketStats {
+    // Recent settled trade prices per resource type, oldest first
+    recent_prices: HashMap<String, VecDeque<u128>>,
+    window: usize,
+}
+
+impl Default for MarketStats {
+    fn default() -> Self {
+        // A zero window would drop every trade as soon as it was recorded
+        MarketStats { recent_prices: HashMap::new(), window: MARKET_STATS_WINDOW }
+    }
+}
+
+impl MarketStats {
+    fn record_trade(&mut self, resource_type: &str, price_per_hour: u128) {
+        let prices = self.recent_prices.entry(resource_type.to_string()).or_default();
+        prices.push_back(price_per_hour);
+        while prices.len() > self.window {
+            prices.pop_front();
+        }
+    }
+
+    fn rolling_average(&self, resource_type: &str) -> Option<u128> {
+        let prices = self.recent_prices.get(resource_type).filter(|p| !p.is_empty())?;
+        Some(prices.iter().sum::<u128>() / prices.len() as u128)
+    }
+}
+
+fn target_price(strategy: &PricingStrategy, stats: &MarketStats, resource_type: &str, utilization: f64) -> Option<u128> {
+    match strategy {
+        PricingStrategy::Fixed { price_per_hour } => Some(*price_per_hour),
+        PricingStrategy::MarketBased { floor, ceiling, idle_multiplier, busy_multiplier, .. } => {
+            // No trades yet for this resource type: leave the listed price alone
+            let average = stats.rolling_average(resource_type)? as f64;
+            let multiplier = idle_multiplier + (busy_multiplier - idle_multiplier) * utilization.clamp(0.0, 1.0);
+            Some(((average * multiplier) as u128).clamp(*floor, *ceiling))
+        }
+    }
+}
+
+fn reprice_offers(book: &mut OfferBook, provider: &str, strategy: &PricingStrategy, stats: &MarketStats, utilization: f64) -> Result<Vec<String>, Box<dyn Error>> {
+    let threshold = match strategy {
+        PricingStrategy::MarketBased { reprice_threshold, .. } => *reprice_threshold,
+        PricingStrategy::Fixed { .. } => 0.0,
+    };
+    let ours: Vec<(String, String, u128)> = book.offers.values().filter(|o| o.provider == provider).map(|o| (o.id.clone(), o.resource_type.clone(), o.price_per_hour)).collect();
+
+    let mut repriced = Vec::new();
+    for (id, resource_type, listed) in ours {
+        let Some(target) = target_price(strategy, stats, &resource_type, utilization) else {
+            continue;
+        };
+        // Small drifts are ignored so offers aren't re-published on every trade
+        let drift = (target as f64 - listed as f64).abs() / (listed.max(1) as f64);
+        if target != listed && drift > threshold {
+            amend_offer(book, provider, &id, Some(target), None)?;
+            repriced.push(id);
+        }
+    }
+    Ok(repriced)
+}
+
+fn run_pricing_loop() -> Result<(), Box<dyn Error>> {
+    // Functionality to subscribe to the MarketStats push topic, feed settled trades into
+    // record_trade, and call reprice_offers after each update and whenever utilization from
+    // ProviderStats changes; repriced offers go out as Offers push events
+    Ok(())
+}
+
+// Provider: Priority Scheduling and Fair Share
+// Queued jobs start in priority order whenever capacity frees up, instead of first come first served
+fn job_priority(job: &ProviderJob) -> (bool, u128) {
+    // Reserved before spot; within a class, higher price per core-hour first
+    (job.class == JobClass::Reserved, job.price_per_hour / job.cpu_cores.max(1) as u128)
+}
+
+fn within_fair_share(queue: &JobQueue, job: &ProviderJob) -> bool {
+    let held: u32 = queue.running.iter().filter(|j| j.consumer == job.consumer).map(|j| j.cpu_cores).sum();
+    let others_waiting = queue.queued.iter().any(|j| j.consumer != job.consumer);
+    // The cap only applies while someone else is waiting, so idle capacity is never wasted
+    !others_waiting || (held + job.cpu_cores) as f64 <= queue.total_cores as f64 * queue.policy.max_consumer_share
+}
+
+fn schedule_queued(queue: &mut JobQueue, stats: &mut ProviderStats) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut pending = std::mem::take(&mut queue.queued);
+    // Stable sort, so equal-priority jobs keep their arrival order
+    pending.sort_by(|a, b| job_priority(b).cmp(&job_priority(a)));
+    queue.queued = pending.clone();
+
+    let mut started = Vec::new();
+    for job in pending {
+        if !within_fair_share(queue, &job) {
+            continue;
+        }
+        let (cores, memory) = free_capacity(queue);
+        if (cores < job.cpu_cores || memory < job.memory_gb) && queue.policy.preempt_for_higher_price && job.class == JobClass::Spot {
+            // Cheapest spot jobs go first, and only if they pay enough less than the queued job
+            let mut victims: Vec<(usize, u128)> = queue
+                .running
+                .iter()
+                .enumerate()
+                .filter(|(_, r)| r.class == JobClass::Spot && (r.price_per_hour as f64) * queue.policy.min_price_ratio <= job.price_per_hour as f64)
+                .map(|(i, r)| (i, r.price_per_hour))
+                .collect();
+            victims.sort_by_key(|(_, price)| *price);
+            let reclaimable: (u32, u32) = victims.iter().fold((cores, memory), |(c, m), (i, _)| (c + queue.running[*i].cpu_cores, m + queue.running[*i].memory_gb));
+            // Preempt only if it actually makes room; never kill work for nothing
+            if reclaimable.0 >= job.cpu_cores && reclaimable.1 >= job.memory_gb {
+                while free_capacity(queue).0 < job.cpu_cores || free_capacity(queue).1 < job.memory_gb {
+                    let (index, _) = victims.remove(0);
+                    let victim_id = queue.running[index].id.clone();
+                    preempt(queue, stats, index)?;
+                    // Indices after the removed job shift down by one
+                    victims.iter_mut().filter(|(i, _)| *i > index).for_each(|(i, _)| *i -= 1);
+                    log_activity(&format!("preempted {} for higher-paying {}", victim_id, job.id))?;
+                }
+            }
+        }
+        let (cores, memory) = free_capacity(queue);
+        if cores >= job.cpu_cores && memory >= job.memory_gb {
+            queue.queued.retain(|q| q.id != job.id);
+            started.push(job.id.clone());
+            start_job(queue, stats, job)?;
+        }
+    }
+    Ok(started)
+}
+
+// Provider: Persistent Stats and Prometheus Metrics
+// Job history and earnings survive restarts, and operators scrape /metrics across a fleet
+const JOB_LATENCY_BUCKETS: [f64; 8] = [1.0, 10.0, 60.0, 300.0, 900.0, 3600.0, 14400.0, 86400.0];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum JobOutcome {
+    Completed { earned: u128 },
+    Failed { reason: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct JobRecord {
+    job_id: String,
+    consumer: String,
+    class: JobClass,
+    cpu_cores: u32,
+    outcome: JobOutcome,
+    duration_secs: f64,
+    finished_at: u64,
+}
+
+fn record_job_outcome(stats: &mut ProviderStats, data_dir: &str, job: &JobSpec, outcome: JobOutcome, elapsed: std::time::Duration) -> Result<(), Box<dyn Error>> {
+    use std::io::Write;
+    match &outcome {
+        JobOutcome::Completed { earned } => {
+            stats.jobs_completed += 1;
+            stats.earnings += earned;
+            let secs = elapsed.as_secs_f64();
+            stats.latency_buckets.resize(JOB_LATENCY_BUCKETS.len() + 1, 0);
+            let bucket = JOB_LATENCY_BUCKETS.iter().position(|bound| secs <= *bound).unwrap_or(JOB_LATENCY_BUCKETS.len());
+            stats.latency_buckets[bucket] += 1;
+            stats.latency_sum_secs += secs;
+        }
+        JobOutcome::Failed { .. } => stats.jobs_failed += 1,
+    }
+
+    // History is append-only JSON lines, so a crash loses at most the record being written
+    let record = JobRecord {
+        job_id: job.id.clone(),
+        consumer: job.consumer.clone(),
+        class: job.class,
+        cpu_cores: job.cpu_cores,
+        outcome,
+        duration_secs: elapsed.as_secs_f64(),
+        finished_at: std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_secs(),
+    };
+    let mut history = std::fs::OpenOptions::new().create(true).append(true).open(format!("{}/job_history.jsonl", data_dir))?;
+    writeln!(history, "{}", serde_json::to_string(&record)?)?;
+
+    // Totals are rewritten whole through a temp file, same as the encrypted state store
+    let tmp = format!("{}/provider_stats.json.tmp", data_dir);
+    std::fs::write(&tmp, serde_json::to_vec(stats)?)?;
+    std::fs::rename(tmp, format!("{}/provider_stats.json", data_dir))?;
+    Ok(())
+}
+
+fn load_provider_stats(data_dir: &str) -> Result<ProviderStats, Box<dyn Error>> {
+    match std::fs::read(format!("{}/provider_stats.json", data_dir)) {
+        Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(ProviderStats::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn render_prometheus(provider: &str, stats: &ProviderStats, total_cores: u32) -> String {
+    use std::fmt::Write;
+    let mut out = String::new();
+    let label = format!("provider=\"{}\"", provider);
+    let _ = writeln!(out, "# TYPE daa_provider_jobs_total counter");
+    let _ = writeln!(out, "daa_provider_jobs_total{{{},outcome=\"completed\"}} {}", label, stats.jobs_completed);
+    let _ = writeln!(out, "daa_provider_jobs_total{{{},outcome=\"failed\"}} {}", label, stats.jobs_failed);
+    let _ = writeln!(out, "daa_provider_jobs_total{{{},outcome=\"preempted\"}} {}", label, stats.jobs_preempted);
+
+    let _ = writeln!(out, "# TYPE daa_provider_cpu_utilization gauge");
+    for (class, cores) in &stats.cores_in_use {
+        let _ = writeln!(out, "daa_provider_cpu_utilization{{{},class=\"{:?}\"}} {}", label, class, *cores as f64 / total_cores.max(1) as f64);
+    }
+
+    // rUv has no fractional units, so the counter is exact until it passes 2^53
+    let _ = writeln!(out, "# TYPE daa_provider_earnings_ruv_total counter");
+    let _ = writeln!(out, "daa_provider_earnings_ruv_total{{{}}} {}", label, stats.earnings);
+
+    let _ = writeln!(out, "# TYPE daa_provider_job_duration_seconds histogram");
+    let mut cumulative = 0;
+    for (i, bound) in JOB_LATENCY_BUCKETS.iter().enumerate() {
+        cumulative += stats.latency_buckets.get(i).copied().unwrap_or(0);
+        let _ = writeln!(out, "daa_provider_job_duration_seconds_bucket{{{},le=\"{}\"}} {}", label, bound, cumulative);
+    }
+    cumulative += stats.latency_buckets.get(JOB_LATENCY_BUCKETS.len()).copied().unwrap_or(0);
+    let _ = writeln!(out, "daa_provider_job_duration_seconds_bucket{{{},le=\"+Inf\"}} {}", label, cumulative);
+    let _ = writeln!(out, "daa_provider_job_duration_seconds_sum{{{}}} {}", label, stats.latency_sum_secs);
+    let _ = writeln!(out, "daa_provider_job_duration_seconds_count{{{}}} {}", label, cumulative);
+    out
+}
+
+#[derive(Clone)]
+struct MetricsState {
+    provider: String,
+    total_cores: u32,
+    stats: tokio::sync::watch::Receiver<ProviderStats>,
+}
+
+impl Provider {
+    // Taken before start(), which holds the provider mutably for its whole lifetime
+    fn metrics_state(&self) -> MetricsState {
+        MetricsState { provider: self.account.clone(), total_cores: self.queue.total_cores, stats: self.metrics.subscribe() }
+    }
+}
+
+fn metrics_router(state: MetricsState) -> Router {
+    // Separate from the exchange API router and unauthenticated, as scrapers expect; bind it
+    // to a private interface
+    Router::new()
+        .route(
+            "/metrics",
+            get(|State(state): State<MetricsState>| async move {
+                let stats = state.stats.borrow().clone();
+                ([("content-type", "text/plain; version=0.0.4")], render_prometheus(&state.provider, &stats, state.total_cores))
+            }),
+        )
+        .with_state(state)
+}